@@ -0,0 +1,34 @@
+//! Helpers shared between benchmarks.
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+/// Run `f` repeatedly for at least 0.5s and return megapixels per second of fastest run.
+///
+/// Fastest run is used instead of average to be less sensitive to noise on busy machines.
+pub fn throughput(pixels: usize, mut f: impl FnMut()) -> f64 {
+    let started = Instant::now();
+    let mut fastest = f64::MAX;
+    let mut runs = 0;
+    while runs < 3 || started.elapsed().as_secs_f64() < 0.5 {
+        let run = Instant::now();
+        f();
+        fastest = fastest.min(run.elapsed().as_secs_f64());
+        runs += 1;
+    }
+    pixels as f64 / fastest / 1e6
+}
+
+/// Sorted paths of files in `qoi_test_images` with given extension.
+pub fn test_images(extension: &str) -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("qoi_test_images");
+    let mut paths: Vec<_> = fs::read_dir(dir)
+        .expect("test images are missing")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == extension))
+        .collect();
+    paths.sort();
+    paths
+}
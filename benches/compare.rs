@@ -0,0 +1,100 @@
+//! Encode and decode throughput and output size against reference `qoi.h` in `cc/`.
+//!
+//! Run with `cargo bench --features bench-c --bench compare`.
+use std::{fs, hint::black_box};
+mod common;
+use common::*;
+use qoi::*;
+
+/// Bindings to `cc/qoi.h` compiled by build script.
+mod c {
+    use std::ffi::{c_int, c_uint, c_void};
+
+    #[repr(C)]
+    #[derive(Default)]
+    pub struct QoiDesc {
+        pub width: c_uint,
+        pub height: c_uint,
+        pub channels: u8,
+        pub colorspace: u8,
+    }
+
+    extern "C" {
+        fn qoi_encode(
+            data: *const c_void,
+            desc: *const QoiDesc,
+            out_len: *mut c_int,
+        ) -> *mut c_void;
+        fn qoi_decode(
+            data: *const c_void,
+            size: c_int,
+            desc: *mut QoiDesc,
+            channels: c_int,
+        ) -> *mut c_void;
+        fn free(ptr: *mut c_void);
+    }
+
+    /// Encode pixels and return size of encoded image.
+    pub fn encode(pixels: &[u8], desc: &QoiDesc) -> usize {
+        let mut len = 0;
+        // SAFETY: `pixels` holds `width * height * channels` bytes, result is freed once
+        unsafe {
+            let bytes = qoi_encode(pixels.as_ptr().cast(), desc, &mut len);
+            assert!(!bytes.is_null(), "qoi.h failed to encode");
+            free(bytes);
+        }
+        len as usize
+    }
+
+    /// Decode image and return descriptor.
+    pub fn decode(bytes: &[u8]) -> QoiDesc {
+        let mut desc = QoiDesc::default();
+        // SAFETY: `qoi_decode` reads at most `size` bytes, result is freed once
+        unsafe {
+            let pixels = qoi_decode(bytes.as_ptr().cast(), bytes.len() as c_int, &mut desc, 0);
+            assert!(!pixels.is_null(), "qoi.h failed to decode");
+            free(pixels);
+        }
+        desc
+    }
+}
+
+fn main() {
+    println!(
+        "{:<20} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}",
+        "image", "enc rust", "enc c", "dec rust", "dec c", "size rust", "size c"
+    );
+    for path in test_images("qoi") {
+        let bytes = fs::read(&path).unwrap();
+        let (pixels, desc) = qoi_decode_from_slice(&bytes, None).unwrap();
+        let pixel_count = desc.width * desc.height;
+        let c_desc = c::QoiDesc {
+            width: desc.width as u32,
+            height: desc.height as u32,
+            channels: desc.channels as u8,
+            colorspace: desc.colorspace as u8,
+        };
+
+        let size_rust = qoi_encode(&pixels, &desc).unwrap().len();
+        let size_c = c::encode(&pixels, &c_desc);
+
+        let enc_rust = throughput(pixel_count, || {
+            black_box(qoi_encode(&pixels, &desc).unwrap());
+        });
+        let enc_c = throughput(pixel_count, || {
+            black_box(c::encode(&pixels, &c_desc));
+        });
+        let dec_rust = throughput(pixel_count, || {
+            black_box(qoi_decode_from_slice(&bytes, None).unwrap());
+        });
+        let dec_c = throughput(pixel_count, || {
+            black_box(c::decode(&bytes));
+        });
+
+        let name = path.file_name().unwrap().to_string_lossy();
+        println!(
+            "{name:<20} {enc_rust:>10.1} {enc_c:>10.1} {dec_rust:>10.1} {dec_c:>10.1} {size_rust:>10} {size_c:>10}"
+        );
+    }
+    println!("throughput in MP/s, size in bytes");
+}
@@ -0,0 +1,25 @@
+//! Decode throughput of `qoi_decode` against `qoi_decode_from_slice`.
+//!
+//! Run with `cargo bench --bench decode`.
+use std::{fs, hint::black_box, io::BufReader};
+mod common;
+use common::*;
+use qoi::*;
+
+fn main() {
+    println!("{:<20} {:>14} {:>14}", "image", "read MP/s", "slice MP/s");
+    for path in test_images("qoi") {
+        let bytes = fs::read(&path).unwrap();
+        let desc = qoi_read_header(bytes.as_slice()).unwrap();
+        let pixels = desc.width * desc.height;
+
+        let read = throughput(pixels, || {
+            black_box(qoi_decode(BufReader::new(bytes.as_slice()), None).unwrap());
+        });
+        let slice = throughput(pixels, || {
+            black_box(qoi_decode_from_slice(&bytes, None).unwrap());
+        });
+        let name = path.file_name().unwrap().to_string_lossy();
+        println!("{name:<20} {read:>14.1} {slice:>14.1}");
+    }
+}
@@ -0,0 +1,65 @@
+//! Encode throughput of `qoi_encode` on test images and on synthetic flat screenshot.
+//!
+//! Run with `cargo bench --bench encode`.
+use image::DynamicImage;
+use std::hint::black_box;
+mod common;
+use common::*;
+use qoi::*;
+
+/// 1920x1080 image of flat rectangles like UI screenshot, mostly made of runs.
+fn screenshot(channels: ChanelMode) -> (Vec<u8>, QoiDescriptor) {
+    let (width, height) = (1920, 1080);
+    let mut pixels = Vec::with_capacity(width * height * channels as usize);
+    for y in 0..height {
+        for x in 0..width {
+            let color = match (x / 240 + y / 90) % 5 {
+                0 => [255, 255, 255, 255],
+                1 => [240, 240, 240, 255],
+                2 => [30, 120, 215, 255],
+                3 if x % 7 == 0 => [20, 20, 20, 255],
+                _ => [250, 250, 250, 200],
+            };
+            pixels.extend_from_slice(&color[..channels as usize]);
+        }
+    }
+    let desc = QoiDescriptor {
+        width,
+        height,
+        channels,
+        colorspace: Colorspace::Srgb,
+    };
+    (pixels, desc)
+}
+
+fn main() {
+    let mut images = vec![
+        ("screenshot rgb".to_string(), screenshot(ChanelMode::Rgb)),
+        ("screenshot rgba".to_string(), screenshot(ChanelMode::Rgba)),
+    ];
+    for path in test_images("png") {
+        let image = image::open(&path).unwrap();
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        let (pixels, channels) = match image {
+            DynamicImage::ImageRgb8(image) => (image.into_raw(), ChanelMode::Rgb),
+            image => (image.to_rgba8().into_raw(), ChanelMode::Rgba),
+        };
+        let desc = QoiDescriptor {
+            width,
+            height,
+            channels,
+            colorspace: Colorspace::Srgb,
+        };
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        images.push((name, (pixels, desc)));
+    }
+
+    println!("{:<20} {:>14} {:>12}", "image", "encode MP/s", "size");
+    for (name, (pixels, desc)) in images {
+        let size = qoi_encode(&pixels, &desc).unwrap().len();
+        let encode = throughput(desc.width * desc.height, || {
+            black_box(qoi_encode(&pixels, &desc).unwrap());
+        });
+        println!("{name:<20} {encode:>14.1} {size:>12}");
+    }
+}
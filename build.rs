@@ -0,0 +1,12 @@
+fn main() {
+    // reference `qoi.h` is only needed to compare against in benchmarks
+    #[cfg(feature = "bench-c")]
+    {
+        println!("cargo:rerun-if-changed=cc/qoi.c");
+        println!("cargo:rerun-if-changed=cc/qoi.h");
+        cc::Build::new()
+            .file("cc/qoi.c")
+            .opt_level(3)
+            .compile("qoi_c");
+    }
+}
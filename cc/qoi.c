@@ -0,0 +1,4 @@
+// Reference implementation built by build.rs for benchmarks.
+#define QOI_IMPLEMENTATION
+#define QOI_NO_STDIO
+#include "qoi.h"
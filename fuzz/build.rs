@@ -0,0 +1,9 @@
+fn main() {
+    // reference `qoi.h` to compare against in `qoi-fuzz-reference`
+    println!("cargo:rerun-if-changed=../cc/qoi.c");
+    println!("cargo:rerun-if-changed=../cc/qoi.h");
+    cc::Build::new()
+        .file("../cc/qoi.c")
+        .opt_level(3)
+        .compile("qoi_c");
+}
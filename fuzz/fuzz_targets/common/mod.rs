@@ -0,0 +1,36 @@
+//! Helpers shared between fuzz targets.
+use arbitrary::{Arbitrary, Result, Unstructured};
+use qoi::*;
+
+/// Image with descriptor and pixels generated together so pixels always fit.
+#[derive(Debug)]
+pub struct Image {
+    pub desc: QoiDescriptor,
+    pub pixels: Vec<u8>,
+}
+
+impl<'a> Arbitrary<'a> for Image {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let desc = QoiDescriptor {
+            width: u.int_in_range(1..=256)?,
+            height: u.int_in_range(1..=256)?,
+            channels: arbitrary_channels(u)?,
+            colorspace: match bool::arbitrary(u)? {
+                false => Colorspace::Srgb,
+                true => Colorspace::Linear,
+            },
+        };
+        let len = desc.width * desc.height * desc.channels as usize;
+        // once fuzzer data runs out pixels are zeros, which exercises runs
+        let pixels = (0..len).map(|_| u8::arbitrary(u)).collect::<Result<_>>()?;
+        Ok(Self { desc, pixels })
+    }
+}
+
+/// RGB or RGBA with equal chance.
+pub fn arbitrary_channels(u: &mut Unstructured<'_>) -> Result<ChanelMode> {
+    Ok(match bool::arbitrary(u)? {
+        false => ChanelMode::Rgb,
+        true => ChanelMode::Rgba,
+    })
+}
@@ -0,0 +1,36 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use qoi::*;
+use std::io::Cursor;
+
+fuzz_target!(|data: &[u8]| {
+    // must never panic, whatever is in data
+    let decoded = qoi_decode_from_slice(data, None);
+    let strict = DecodeOptions {
+        strict: true,
+        ..DecodeOptions::default()
+    };
+    let decoded_strict = qoi_decode_from_slice_with_options(data, &strict);
+    if let Ok(stats) = QoiStats::from_qoi(data) {
+        let _ = stats.to_string();
+    }
+
+    // header claiming more pixels than data can hold is rejected by slice decoder up front,
+    // stream decoder grows its buffer with decoded pixels until data runs out
+    if let Ok(desc) = qoi_read_header_from_slice(data) {
+        if desc.width * desc.height > (data.len() - 14) * 62 {
+            assert!(matches!(decoded, Err(QoiError::Truncated)));
+        }
+    }
+
+    let streamed = qoi_decode(Cursor::new(data), None);
+    match (&decoded, streamed) {
+        (Ok(decoded), Ok(streamed)) => assert_eq!(*decoded, streamed),
+        (Err(_), Err(_)) => {}
+        (decoded, streamed) => panic!("slice {decoded:?} != stream {streamed:?}"),
+    }
+    // strict decoding accepts only what lenient one does, with the same pixels
+    if let Ok(strict) = decoded_strict {
+        assert_eq!(Some(&strict), decoded.as_ref().ok());
+    }
+});
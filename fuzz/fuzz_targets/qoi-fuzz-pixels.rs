@@ -1,8 +1,7 @@
 #![no_main]
-mod qoi;
+use libfuzzer_sys::fuzz_target;
 use qoi::*;
 use std::io::Cursor;
-use libfuzzer_sys::fuzz_target;
 
 fuzz_target!(|pixels: &[u8]| {
     // fuzzed code goes
@@ -15,7 +14,7 @@ fuzz_target!(|pixels: &[u8]| {
         channels: ChanelMode::Rgb,
         colorspace: Colorspace::Linear,
     };
-    let bytes = qoi_encode(&pixels, desc.clone()).unwrap();
+    let bytes = qoi_encode(pixels, &desc).unwrap();
     let (pixels_, _desc) = qoi_decode(Cursor::new(bytes), None).unwrap();
     assert_eq!(pixels_, pixels);
 });
@@ -0,0 +1,252 @@
+#![no_main]
+//! Differential fuzzing against reference `qoi.h` in `cc/`, built by build script.
+use arbitrary::{Arbitrary, Result, Unstructured};
+use libfuzzer_sys::fuzz_target;
+use qoi::*;
+use std::io::Cursor;
+mod common;
+use common::*;
+
+/// Bindings to `cc/qoi.h`.
+mod c {
+    use qoi::*;
+    use std::ffi::{c_int, c_uint, c_void};
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct QoiDesc {
+        width: c_uint,
+        height: c_uint,
+        channels: u8,
+        colorspace: u8,
+    }
+
+    extern "C" {
+        fn qoi_encode(
+            data: *const c_void,
+            desc: *const QoiDesc,
+            out_len: *mut c_int,
+        ) -> *mut c_void;
+        fn qoi_decode(
+            data: *const c_void,
+            size: c_int,
+            desc: *mut QoiDesc,
+            channels: c_int,
+        ) -> *mut c_void;
+        fn free(ptr: *mut c_void);
+    }
+
+    /// Copy `len` bytes of `ptr` allocated by `qoi.h` and free it.
+    ///
+    /// # Safety
+    /// `ptr` must be null or point to at least `len` bytes allocated with `malloc`.
+    unsafe fn take(ptr: *mut c_void, len: usize) -> Option<Vec<u8>> {
+        if ptr.is_null() {
+            return None;
+        }
+        // SAFETY: guaranteed by caller
+        let bytes = unsafe { std::slice::from_raw_parts(ptr.cast::<u8>(), len).to_vec() };
+        unsafe { free(ptr) };
+        Some(bytes)
+    }
+
+    /// Encode pixels, `None` if `qoi.h` refused them.
+    pub fn encode(pixels: &[u8], desc: &QoiDescriptor) -> Option<Vec<u8>> {
+        assert_eq!(
+            pixels.len(),
+            desc.width * desc.height * desc.channels as usize
+        );
+        let desc = QoiDesc {
+            width: desc.width as c_uint,
+            height: desc.height as c_uint,
+            channels: desc.channels as u8,
+            colorspace: desc.colorspace as u8,
+        };
+        let mut len = 0;
+        // SAFETY: `pixels` holds `width * height * channels` bytes,
+        // result has `len` bytes
+        unsafe {
+            let bytes = qoi_encode(pixels.as_ptr().cast(), &desc, &mut len);
+            take(bytes, len as usize)
+        }
+    }
+
+    /// Decode image with `channels` or channels of file when `None`.
+    ///
+    /// Like in this crate `channels` of returned descriptor are channels of pixels,
+    /// `qoi.h` gives channels of file.
+    pub fn decode(bytes: &[u8], channels: Option<ChanelMode>) -> Option<(Vec<u8>, QoiDescriptor)> {
+        let mut desc = QoiDesc::default();
+        let channels = channels.map_or(0, |channels| channels as c_int);
+        // SAFETY: `qoi_decode` reads at most `size` bytes, result has
+        // `width * height * channels` bytes
+        let pixels = unsafe {
+            let pixels = qoi_decode(
+                bytes.as_ptr().cast(),
+                bytes.len() as c_int,
+                &mut desc,
+                channels,
+            );
+            // desc is filled even for headers `qoi.h` rejects, its size may overflow
+            if pixels.is_null() {
+                return None;
+            }
+            if channels != 0 {
+                desc.channels = channels as u8;
+            }
+            let len = desc.width as usize * desc.height as usize * desc.channels as usize;
+            take(pixels, len)?
+        };
+        let desc = QoiDescriptor {
+            width: desc.width as usize,
+            height: desc.height as usize,
+            channels: match desc.channels {
+                3 => ChanelMode::Rgb,
+                _ => ChanelMode::Rgba,
+            },
+            colorspace: match desc.colorspace {
+                0 => Colorspace::Srgb,
+                _ => Colorspace::Linear,
+            },
+        };
+        Some((pixels, desc))
+    }
+}
+
+/// Valid QOI stream made of arbitrary ops, with size of image matching pixels they give.
+#[derive(Debug)]
+struct Stream {
+    bytes: Vec<u8>,
+}
+
+impl<'a> Arbitrary<'a> for Stream {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let channels = arbitrary_channels(u)?;
+        let colorspace = u8::from(bool::arbitrary(u)?);
+        let height = u.int_in_range(1..=16)?;
+        let mut ops: Vec<u8> = Vec::arbitrary(u)?;
+
+        // keep whole ops only and count their pixels
+        let (mut len, mut pixels) = (0, 0);
+        while let Some(&op) = ops.get(len) {
+            let (op_len, op_pixels) = match op {
+                0xfe => (4, 1),
+                0xff => (5, 1),
+                0x80..=0xbf => (2, 1),
+                0xc0..=0xfd => (1, (op & 0x3f) as usize + 1),
+                _ => (1, 1),
+            };
+            if len + op_len > ops.len() {
+                break;
+            }
+            len += op_len;
+            pixels += op_pixels;
+        }
+        ops.truncate(len);
+        // pad with single pixel runs so pixels make whole rows
+        if pixels == 0 || pixels % height != 0 {
+            let missing = height - pixels % height;
+            ops.resize(len + missing, 0xc0);
+            pixels += missing;
+        }
+
+        let mut bytes = b"qoif".to_vec();
+        bytes.extend((pixels as u32 / height as u32).to_be_bytes());
+        bytes.extend((height as u32).to_be_bytes());
+        bytes.extend([channels as u8, colorspace]);
+        bytes.extend(ops);
+        bytes.extend([0, 0, 0, 0, 0, 0, 0, 1]);
+        Ok(Self { bytes })
+    }
+}
+
+/// Raw inputs with images bigger than this are skipped, `qoi.h` allocates whole image
+/// even when there are just a few bytes of ops.
+const RAW_PIXELS_MAX: usize = 1 << 20;
+
+/// Size of header and of padding, `qoi.h` rejects anything shorter.
+const QOI_MIN_SIZE: usize = 14 + 8;
+
+#[derive(Debug, Arbitrary)]
+enum Input {
+    Encode(Image),
+    Decode(Stream),
+    Raw(Vec<u8>),
+}
+
+fuzz_target!(|input: Input| {
+    match input {
+        Input::Encode(Image { desc, pixels }) => {
+            let bytes = qoi_encode(&pixels, &desc).unwrap();
+            assert_eq!(Some(bytes), c::encode(&pixels, &desc));
+        }
+        Input::Decode(Stream { bytes }) => {
+            let strict = DecodeOptions {
+                strict: true,
+                ..DecodeOptions::default()
+            };
+            for channels in [None, Some(ChanelMode::Rgb), Some(ChanelMode::Rgba)] {
+                let options = DecodeOptions {
+                    layout: channels.map(PixelLayout::from),
+                    ..strict
+                };
+                let expected = c::decode(&bytes, channels).unwrap();
+                let decoded = qoi_decode_from_slice_with_options(&bytes, &options).unwrap();
+                assert_eq!(decoded, expected);
+                let streamed = qoi_decode_with_options(Cursor::new(&bytes), &options).unwrap();
+                assert_eq!(streamed, expected);
+            }
+        }
+        Input::Raw(bytes) => {
+            let header = qoi_read_header_from_slice(&bytes);
+            if matches!(&header, Ok(desc) if desc.width * desc.height > RAW_PIXELS_MAX) {
+                return;
+            }
+            for channels in [None, Some(ChanelMode::Rgb), Some(ChanelMode::Rgba)] {
+                let options = DecodeOptions {
+                    layout: channels.map(PixelLayout::from),
+                    ..DecodeOptions::default()
+                };
+                let strict = DecodeOptions {
+                    strict: true,
+                    ..options
+                };
+                let expected = c::decode(&bytes, channels);
+                assert_eq!(
+                    expected.is_some(),
+                    header.is_ok() && bytes.len() >= QOI_MIN_SIZE
+                );
+                let Some((expected, desc)) = expected else {
+                    assert!(qoi_decode_from_slice_with_options(&bytes, &strict).is_err());
+                    continue;
+                };
+
+                // qoi.h reads ops up to the last 8 bytes, where the last op may end,
+                // and repeats the last pixel after them
+                let ops = &bytes[..bytes.len() - 8];
+                match qoi_decode_from_slice_recover(ops, &options, [0; 4]) {
+                    Ok(image) => {
+                        assert_eq!(image.desc, desc);
+                        let pixel_size = desc.channels as usize;
+                        let (decoded, rest) = expected.split_at(image.recovered * pixel_size);
+                        assert_eq!(decoded, &image.pixels[..decoded.len()]);
+                        if let Some(last) = rest.get(..pixel_size) {
+                            assert!(rest.chunks(last.len()).all(|pixel| pixel == last));
+                        }
+                    }
+                    // images much bigger than ops could hold aren't filled
+                    Err(err) => assert!(matches!(err, QoiError::Truncated)),
+                }
+
+                // streams valid for strict decoding are decoded the same
+                let expected = (expected, desc);
+                if let Ok(decoded) = qoi_decode_from_slice_with_options(&bytes, &strict) {
+                    assert_eq!(decoded, expected);
+                }
+                if let Ok(streamed) = qoi_decode_with_options(Cursor::new(&bytes), &strict) {
+                    assert_eq!(streamed, expected);
+                }
+            }
+        }
+    }
+});
@@ -0,0 +1,30 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use qoi::*;
+use std::io::Cursor;
+mod common;
+use common::*;
+
+fuzz_target!(|image: Image| {
+    let Image { desc, pixels } = image;
+    let bytes = qoi_encode(&pixels, &desc).unwrap();
+
+    let mut streamed = Vec::new();
+    let mut encoder = QoiEncoder::new(&mut streamed, &desc).unwrap();
+    for row in pixels.chunks(desc.width * desc.channels as usize) {
+        encoder.write_row(row).unwrap();
+    }
+    encoder.finish().unwrap();
+    assert_eq!(streamed, bytes);
+
+    let strict = DecodeOptions {
+        strict: true,
+        ..DecodeOptions::default()
+    };
+    let (decoded, decoded_desc) = qoi_decode_from_slice_with_options(&bytes, &strict).unwrap();
+    assert_eq!(decoded_desc, desc);
+    assert_eq!(decoded, pixels);
+
+    let (decoded, _) = qoi_decode_with_options(Cursor::new(&bytes), &strict).unwrap();
+    assert_eq!(decoded, pixels);
+});
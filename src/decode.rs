@@ -0,0 +1,982 @@
+//! QOI decoder.
+use super::*;
+#[cfg(feature = "alloc")]
+use alloc::{vec, vec::Vec};
+#[cfg(feature = "std")]
+use std::io::Read;
+
+/// Check that file starts with `qoif`.
+fn check_magic(magic: [u8; 4]) -> Result<(), QoiError> {
+    if u32::from_be_bytes(magic) != u32::from_be_bytes(*b"qoif") {
+        return Err(QoiError::BadMagic(magic));
+    }
+    Ok(())
+}
+
+/// Parse and validate 14-byte header of QOI image with at most `max_pixels` pixels.
+fn parse_header(
+    header: &[u8; QOI_HEADER_SIZE],
+    max_pixels: usize,
+) -> Result<QoiDescriptor, QoiError> {
+    check_magic([header[0], header[1], header[2], header[3]])?;
+
+    let width = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+    let height = u32::from_be_bytes([header[8], header[9], header[10], header[11]]) as usize;
+
+    let channels = match header[12] {
+        3 => ChanelMode::Rgb,
+        4 => ChanelMode::Rgba,
+        channels => {
+            return Err(QoiError::InvalidChannels(channels));
+        }
+    };
+
+    let colorspace = match header[13] {
+        0 => Colorspace::Srgb,
+        1 => Colorspace::Linear,
+        colorspace => {
+            return Err(QoiError::InvalidColorspace(colorspace));
+        }
+    };
+
+    let desc = QoiDescriptor {
+        width,
+        height,
+        channels,
+        colorspace,
+    };
+    pixel_count(&desc, max_pixels)?;
+    Ok(desc)
+}
+
+/// Read and validate 14-byte header of QOI image without decoding any pixels.
+#[cfg(feature = "std")]
+pub fn qoi_read_header(data: impl Read) -> Result<QoiDescriptor, QoiError> {
+    read_header(data, DEFAULT_MAX_PIXELS)
+}
+
+/// Read header of QOI image and check it against `limits`, which may raise `max_pixels`
+/// past `QOI_PIXELS_MAX`.
+#[cfg(feature = "std")]
+pub fn qoi_read_header_with_limits(
+    data: impl Read,
+    limits: DecodeLimits,
+) -> Result<QoiDescriptor, QoiError> {
+    let desc = read_header(data, limits.header_max_pixels())?;
+    limits.check(&desc, desc.channels as usize)?;
+    Ok(desc)
+}
+
+#[cfg(feature = "std")]
+fn read_header(mut data: impl Read, max_pixels: usize) -> Result<QoiDescriptor, QoiError> {
+    let mut header = [0u8; QOI_HEADER_SIZE];
+    read_stream(&mut data, &mut header[..4])?;
+    check_magic([header[0], header[1], header[2], header[3]])?;
+    read_stream(&mut data, &mut header[4..])?;
+    parse_header(&header, max_pixels)
+}
+
+/// Fill `buf` from QOI stream, which is truncated if `data` ends before that.
+#[cfg(feature = "std")]
+fn read_stream(mut data: impl Read, buf: &mut [u8]) -> Result<(), QoiError> {
+    data.read_exact(buf).map_err(|err| match err.kind() {
+        std::io::ErrorKind::UnexpectedEof => QoiError::Truncated,
+        _ => QoiError::Io(err),
+    })
+}
+
+/// Validate header at start of `data` without decoding any pixels.
+pub fn qoi_read_header_from_slice(data: &[u8]) -> Result<QoiDescriptor, QoiError> {
+    read_header_from_slice(data, DEFAULT_MAX_PIXELS)
+}
+
+/// Validate header at start of `data` and check it against `limits`, like
+/// [`qoi_read_header_with_limits`](fn.qoi_read_header_with_limits.html).
+pub fn qoi_read_header_from_slice_with_limits(
+    data: &[u8],
+    limits: DecodeLimits,
+) -> Result<QoiDescriptor, QoiError> {
+    let desc = read_header_from_slice(data, limits.header_max_pixels())?;
+    limits.check(&desc, desc.channels as usize)?;
+    Ok(desc)
+}
+
+pub(crate) fn read_header_from_slice(
+    data: &[u8],
+    max_pixels: usize,
+) -> Result<QoiDescriptor, QoiError> {
+    if let Some(magic) = data.get(..4) {
+        check_magic(magic.try_into().unwrap())?;
+    }
+    let header = data.get(..QOI_HEADER_SIZE).ok_or(QoiError::Truncated)?;
+    parse_header(header.try_into().unwrap(), max_pixels)
+}
+
+/// Options of decoding, `Default` gives the same behaviour as [`qoi_decode`](fn.qoi_decode.html).
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct DecodeOptions {
+    /// Layout of decoded pixels, RGB or RGBA by channels of image when `None`.
+    pub layout: Option<PixelLayout>,
+    /// Reject files with missing or wrong `QOI_PADDING`, bytes after it or run going
+    /// past the last pixel, which are ignored otherwise.
+    pub strict: bool,
+    /// How colored pixels are decoded into `PixelLayout::L` or `PixelLayout::La`,
+    /// they are rejected by default.
+    pub gray: GrayMode,
+    /// Limits checked against header before any pixels are decoded.
+    pub limits: DecodeLimits,
+    /// Colorspace to convert pixels to from colorspace of image, which is kept when `None`.
+    ///
+    /// Returned descriptor has the colorspace of decoded pixels.
+    pub colorspace: Option<Colorspace>,
+}
+
+/// Limits of images accepted by decoder, to reject files from untrusted sources
+/// which claim to be huge before allocating memory for them.
+///
+/// `Default` has no limits besides `QOI_PIXELS_MAX`, trusted callers can raise
+/// `max_pixels` past it on 64-bit targets to decode bigger images.
+///
+/// ### Example of rejecting too big image:
+/// ```
+/// use qoi::*;
+///
+/// let bytes = std::fs::read("qoi_test_images/wikipedia_008.qoi").unwrap();
+/// let options = DecodeOptions {
+///     limits: DecodeLimits {
+///         max_bytes: 1 << 20,
+///         ..DecodeLimits::default()
+///     },
+///     ..DecodeOptions::default()
+/// };
+/// let err = qoi_decode_from_slice_with_options(&bytes, &options).unwrap_err();
+/// assert!(matches!(err, QoiError::LimitsExceeded));
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DecodeLimits {
+    pub max_width: usize,
+    pub max_height: usize,
+    /// Maximum of `width * height`, `usize::MAX` removes the limit on 64-bit targets.
+    pub max_pixels: usize,
+    /// Maximum size of decoded pixels in bytes, in layout they are decoded in.
+    pub max_bytes: usize,
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        Self {
+            max_width: usize::MAX,
+            max_height: usize::MAX,
+            max_pixels: DEFAULT_MAX_PIXELS,
+            max_bytes: usize::MAX,
+        }
+    }
+}
+
+impl DecodeLimits {
+    /// Maximum pixels of image which header is parsed, lower limits are checked
+    /// by [`check`](#method.check) to tell them apart from invalid headers.
+    fn header_max_pixels(&self) -> usize {
+        self.max_pixels.max(DEFAULT_MAX_PIXELS)
+    }
+
+    /// Check image of valid `desc` with pixels of `pixel_size` bytes against limits.
+    fn check(&self, desc: &QoiDescriptor, pixel_size: usize) -> Result<(), QoiError> {
+        let pixels = desc.width * desc.height;
+        let within = desc.width <= self.max_width
+            && desc.height <= self.max_height
+            && pixels <= self.max_pixels
+            && pixels
+                .checked_mul(pixel_size)
+                .is_some_and(|bytes| bytes <= self.max_bytes);
+        if !within {
+            return Err(QoiError::LimitsExceeded);
+        }
+        Ok(())
+    }
+}
+
+/// Parse header at start of `data` and check it against `options`, see [`resolve_header`].
+fn header_with_options(
+    data: &[u8],
+    options: &DecodeOptions,
+) -> Result<(QoiDescriptor, PixelLayout, Colorspace), QoiError> {
+    let desc = read_header_from_slice(data, options.limits.header_max_pixels())?;
+    resolve_header(desc, options)
+}
+
+/// Check `desc` of header parsed with `header_max_pixels` of limits against `options`.
+///
+/// Returns descriptor with channels and colorspace of decoded pixels, their layout
+/// and colorspace of image.
+fn resolve_header(
+    mut desc: QoiDescriptor,
+    options: &DecodeOptions,
+) -> Result<(QoiDescriptor, PixelLayout, Colorspace), QoiError> {
+    let layout = options.layout.unwrap_or(desc.channels.into());
+    desc.channels = layout.channels();
+    options.limits.check(&desc, layout.bytes_per_pixel())?;
+    let colorspace = desc.colorspace;
+    desc.colorspace = options.colorspace.unwrap_or(colorspace);
+    Ok((desc, layout, colorspace))
+}
+
+/// Decode a QOI image from `impl Read`.
+///
+/// Will take `ChanelMode` form descriptor of file if not provided, overwise will use provided.
+#[cfg(feature = "std")]
+pub fn qoi_decode(
+    data: impl Read,
+    channels: Option<ChanelMode>,
+) -> Result<(Vec<u8>, QoiDescriptor), QoiError> {
+    let mut decoder = QoiDecoder::new(data, channels)?;
+    decode_all(&mut decoder)
+}
+
+/// Decode a QOI image from `impl Read` into pixels stored in `layout`.
+///
+/// `channels` of returned descriptor are the channels of `layout`.
+///
+/// ### Example of decoding gray image:
+/// ```
+/// use qoi::*;
+///
+/// let desc = QoiDescriptor {
+///     width: 3,
+///     height: 1,
+///     channels: ChanelMode::Rgb,
+///     colorspace: Colorspace::Srgb,
+/// };
+/// let mask = [0, 128, 255];
+/// let bytes = qoi_encode_with_layout(&mask, &desc, PixelLayout::L).unwrap();
+/// let (pixels, _) = qoi_decode_with_layout(&bytes[..], PixelLayout::L).unwrap();
+/// assert_eq!(pixels, mask);
+///
+/// let colored = qoi_encode(&[255, 0, 0], &QoiDescriptor { width: 1, ..desc }).unwrap();
+/// let err = qoi_decode_with_layout(&colored[..], PixelLayout::L).unwrap_err();
+/// assert!(matches!(err, QoiError::NotGray));
+/// let options = DecodeOptions {
+///     layout: Some(PixelLayout::L),
+///     gray: GrayMode::Lossy,
+///     ..DecodeOptions::default()
+/// };
+/// let (pixels, _) = qoi_decode_with_options(&colored[..], &options).unwrap();
+/// assert_eq!(pixels, [77]);
+/// ```
+#[cfg(feature = "std")]
+pub fn qoi_decode_with_layout(
+    data: impl Read,
+    layout: PixelLayout,
+) -> Result<(Vec<u8>, QoiDescriptor), QoiError> {
+    let mut decoder = QoiDecoder::with_layout(data, layout)?;
+    decode_all(&mut decoder)
+}
+
+/// Decode a QOI image from `impl Read` with `options`.
+///
+/// `channels` of returned descriptor are the channels of the layout pixels are decoded in.
+#[cfg(feature = "std")]
+pub fn qoi_decode_with_options(
+    data: impl Read,
+    options: &DecodeOptions,
+) -> Result<(Vec<u8>, QoiDescriptor), QoiError> {
+    let mut decoder = QoiDecoder::with_options(data, options)?;
+    decode_all(&mut decoder)
+}
+
+/// Decode every remaining pixel of `decoder` into new buffer.
+///
+/// Size in header isn't trusted, buffer at most doubles at a time as pixels are decoded
+/// so truncated file can't make us allocate much more than it has pixels.
+#[cfg(feature = "std")]
+pub(crate) fn decode_all<R: Read>(
+    decoder: &mut QoiDecoder<R>,
+) -> Result<(Vec<u8>, QoiDescriptor), QoiError> {
+    const MIN_GROW: usize = 1 << 14;
+    let desc = decoder.desc().clone();
+    let pixel_size = decoder.layout.bytes_per_pixel();
+
+    let total = decoder.rows_left() * desc.width;
+    let mut pixels = Vec::new();
+    let mut decoded = 0;
+    while decoded < total {
+        let count = decoded.max(MIN_GROW).min(total - decoded);
+        pixels.resize((decoded + count) * pixel_size, 0);
+        decoder.decode_pixels(&mut pixels[decoded * pixel_size..])?;
+        decoded += count;
+    }
+    decoder.finish_rows(decoder.rows_left())?;
+
+    Ok((pixels, desc))
+}
+
+/// Decode a QOI image from `impl Read` into caller-provided `buf`.
+///
+/// Without `stride` rows are tightly packed and `buf` must have exactly the size of image.
+/// With `stride` each row starts `stride` bytes after previous one, bytes between rows
+/// are left untouched and `buf` must be at least `stride * (height - 1) + row_len` long.
+/// `channels` has the same meaning as in [`qoi_decode`](fn.qoi_decode.html).
+#[cfg(feature = "std")]
+pub fn qoi_decode_into(
+    data: impl Read,
+    buf: &mut [u8],
+    channels: Option<ChanelMode>,
+    stride: Option<usize>,
+) -> Result<QoiDescriptor, QoiError> {
+    let mut decoder = QoiDecoder::new(data, channels)?;
+    let desc = decoder.desc().clone();
+    let row_len = decoder.row_len();
+
+    let Some(stride) = stride else {
+        let expected = row_len * desc.height;
+        if buf.len() != expected {
+            return Err(QoiError::BufferSizeMismatch {
+                expected,
+                actual: buf.len(),
+            });
+        }
+        decoder.read_rows(buf)?;
+        return Ok(desc);
+    };
+
+    if stride < row_len {
+        return Err(QoiError::InvalidStride { row_len, stride });
+    }
+    let expected = stride
+        .checked_mul(desc.height - 1)
+        .and_then(|len| len.checked_add(row_len))
+        .ok_or(QoiError::TooManyPixels)?;
+    if buf.len() < expected {
+        return Err(QoiError::BufferSizeMismatch {
+            expected,
+            actual: buf.len(),
+        });
+    }
+    for row in 0..desc.height {
+        let start = row * stride;
+        decoder.read_rows(&mut buf[start..start + row_len])?;
+    }
+    Ok(desc)
+}
+
+/// Decode a QOI image from bytes in memory.
+///
+/// Produces the same result as [`qoi_decode`](fn.qoi_decode.html) but indexes `data`
+/// directly instead of reading every byte through `Read`, which is much faster.
+#[cfg(feature = "alloc")]
+pub fn qoi_decode_from_slice(
+    data: &[u8],
+    channels: Option<ChanelMode>,
+) -> Result<(Vec<u8>, QoiDescriptor), QoiError> {
+    let options = DecodeOptions {
+        layout: channels.map(PixelLayout::from),
+        ..DecodeOptions::default()
+    };
+    qoi_decode_from_slice_with_options(data, &options)
+}
+
+/// Decode a QOI image from bytes in memory into pixels stored in `layout`.
+///
+/// `channels` of returned descriptor are the channels of `layout`.
+#[cfg(feature = "alloc")]
+pub fn qoi_decode_from_slice_with_layout(
+    data: &[u8],
+    layout: PixelLayout,
+) -> Result<(Vec<u8>, QoiDescriptor), QoiError> {
+    let options = DecodeOptions {
+        layout: Some(layout),
+        ..DecodeOptions::default()
+    };
+    qoi_decode_from_slice_with_options(data, &options)
+}
+
+/// Decode a QOI image from bytes in memory with `options`.
+///
+/// ### Example of rejecting file with data after the end:
+/// ```
+/// use qoi::*;
+///
+/// let mut bytes = std::fs::read("qoi_test_images/wikipedia_008.qoi").unwrap();
+/// bytes.push(0);
+/// let strict = DecodeOptions {
+///     strict: true,
+///     ..DecodeOptions::default()
+/// };
+/// let err = qoi_decode_from_slice_with_options(&bytes, &strict).unwrap_err();
+/// assert!(matches!(err, QoiError::TrailingData));
+/// assert!(qoi_decode_from_slice_with_options(&bytes, &DecodeOptions::default()).is_ok());
+/// ```
+#[cfg(feature = "alloc")]
+pub fn qoi_decode_from_slice_with_options(
+    data: &[u8],
+    options: &DecodeOptions,
+) -> Result<(Vec<u8>, QoiDescriptor), QoiError> {
+    let (desc, layout, _) = header_with_options(data, options)?;
+    let pixel_count = desc.width * desc.height;
+    // every op byte gives at most 62 pixels, so header of tiny file can't make us
+    // allocate buffer for image which would be truncated anyway
+    if pixel_count.div_ceil(62) > data.len() - QOI_HEADER_SIZE {
+        return Err(QoiError::Truncated);
+    }
+    let pixel_len = pixel_count
+        .checked_mul(layout.bytes_per_pixel())
+        .ok_or(QoiError::TooManyPixels)?;
+    let mut pixels = vec![0; pixel_len];
+    qoi_decode_from_slice_into_with_options(data, &mut pixels, options)?;
+
+    Ok((pixels, desc))
+}
+
+/// Decode a QOI image from bytes in memory into caller-provided `buf`.
+///
+/// Doesn't allocate, `buf` must have exactly the size of image.
+/// `channels` has the same meaning as in [`qoi_decode`](fn.qoi_decode.html).
+pub fn qoi_decode_from_slice_into(
+    data: &[u8],
+    buf: &mut [u8],
+    channels: Option<ChanelMode>,
+) -> Result<QoiDescriptor, QoiError> {
+    let options = DecodeOptions {
+        layout: channels.map(PixelLayout::from),
+        ..DecodeOptions::default()
+    };
+    qoi_decode_from_slice_into_with_options(data, buf, &options)
+}
+
+/// Same as [`qoi_decode_from_slice_into`](fn.qoi_decode_from_slice_into.html)
+/// but pixels are written in `layout`.
+pub fn qoi_decode_from_slice_into_with_layout(
+    data: &[u8],
+    buf: &mut [u8],
+    layout: PixelLayout,
+) -> Result<QoiDescriptor, QoiError> {
+    let options = DecodeOptions {
+        layout: Some(layout),
+        ..DecodeOptions::default()
+    };
+    qoi_decode_from_slice_into_with_options(data, buf, &options)
+}
+
+/// Same as [`qoi_decode_from_slice_into`](fn.qoi_decode_from_slice_into.html)
+/// but with `options`.
+pub fn qoi_decode_from_slice_into_with_options(
+    data: &[u8],
+    buf: &mut [u8],
+    options: &DecodeOptions,
+) -> Result<QoiDescriptor, QoiError> {
+    let (desc, decoded) = decode_slice_into(data, buf, options)?;
+    if decoded < desc.width * desc.height {
+        return Err(QoiError::Truncated);
+    }
+    Ok(desc)
+}
+
+/// How many times more pixels than data could hold are filled by
+/// [`qoi_decode_from_slice_recover`](fn.qoi_decode_from_slice_recover.html).
+#[cfg(feature = "alloc")]
+const RECOVER_FILL_RATIO: usize = 4;
+
+/// Image returned by [`qoi_decode_from_slice_recover`](fn.qoi_decode_from_slice_recover.html).
+#[cfg(feature = "alloc")]
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct RecoveredImage {
+    pub pixels: Vec<u8>,
+    pub desc: QoiDescriptor,
+    /// Number of pixels decoded from data, pixels after them are filled.
+    pub recovered: usize,
+}
+
+/// Decode as much of possibly truncated QOI image as there is and fill the rest
+/// with `fill` color given in RGBA.
+/// In gray layouts `fill` is converted to gray like with `GrayMode::Lossy`.
+///
+/// Only header must be complete, pixels of op cut in the middle are filled too.
+/// Buffer for the whole image is allocated up front, so image is recovered only if it has
+/// at most 4 times as many pixels as `data` could hold, 62 per byte of ops, and
+/// `QoiError::Truncated` is returned otherwise. Tiny file claiming huge image can't make
+/// us allocate more than about 1000 bytes per byte of it.
+///
+/// ### Example of recovering half of image:
+/// ```
+/// use qoi::*;
+///
+/// let bytes = std::fs::read("qoi_test_images/wikipedia_008.qoi").unwrap();
+/// let half = &bytes[..bytes.len() / 2];
+/// let image = qoi_decode_from_slice_recover(half, &DecodeOptions::default(), [255, 0, 255, 255])
+///     .unwrap();
+/// assert!(image.recovered > 0 && image.recovered < image.desc.width * image.desc.height);
+/// ```
+#[cfg(feature = "alloc")]
+pub fn qoi_decode_from_slice_recover(
+    data: &[u8],
+    options: &DecodeOptions,
+    fill: [u8; 4],
+) -> Result<RecoveredImage, QoiError> {
+    let (desc, layout, _) = header_with_options(data, options)?;
+    let pixel_size = layout.bytes_per_pixel();
+    let pixel_count = desc.width * desc.height;
+    let max_recovered = (data.len() - QOI_HEADER_SIZE).saturating_mul(62);
+    if pixel_count.div_ceil(RECOVER_FILL_RATIO) > max_recovered {
+        return Err(QoiError::Truncated);
+    }
+
+    // fill isn't decoded from image, so it is turned to gray even in strict mode
+    let [r, g, b, a] = fill;
+    let fill = layout
+        .write(QoiRGBA::new(r, g, b, a), GrayMode::Lossy)
+        .ok_or(QoiError::NotGray)?;
+
+    let pixel_len = pixel_count
+        .checked_mul(pixel_size)
+        .ok_or(QoiError::TooManyPixels)?;
+    let mut pixels = vec![0; pixel_len];
+    let (desc, recovered) = decode_slice_into(data, &mut pixels, options)?;
+
+    for pixel in pixels[recovered * pixel_size..].chunks_exact_mut(pixel_size) {
+        pixel.copy_from_slice(&fill[..pixel_size]);
+    }
+    Ok(RecoveredImage {
+        pixels,
+        desc,
+        recovered,
+    })
+}
+
+/// Same as [`qoi_decode_from_slice_recover`](fn.qoi_decode_from_slice_recover.html)
+/// but whole `impl Read` is read first, image is limited by size of what was read.
+#[cfg(feature = "std")]
+pub fn qoi_decode_recover(
+    mut data: impl Read,
+    options: &DecodeOptions,
+    fill: [u8; 4],
+) -> Result<RecoveredImage, QoiError> {
+    let mut bytes = Vec::new();
+    data.read_to_end(&mut bytes)?;
+    qoi_decode_from_slice_recover(&bytes, options, fill)
+}
+
+/// Decode pixels into `buf` and return descriptor and number of decoded pixels,
+/// which is less than size of image only if `data` is truncated.
+fn decode_slice_into(
+    data: &[u8],
+    buf: &mut [u8],
+    options: &DecodeOptions,
+) -> Result<(QoiDescriptor, usize), QoiError> {
+    let (desc, layout, colorspace) = header_with_options(data, options)?;
+    let expected = (desc.width * desc.height)
+        .checked_mul(layout.bytes_per_pixel())
+        .ok_or(QoiError::TooManyPixels)?;
+    if buf.len() != expected {
+        return Err(QoiError::BufferSizeMismatch {
+            expected,
+            actual: buf.len(),
+        });
+    }
+
+    // each layout gets its own copy of the loop so swizzling is resolved at compile time
+    use PixelLayout::*;
+    let ops = &data[QOI_HEADER_SIZE..];
+    let (len, decoded) = match layout {
+        Rgb => decode_slice::<3, StoreRgb>(ops, buf, options)?,
+        Rgba => decode_slice::<4, StoreRgba>(ops, buf, options)?,
+        Bgr => decode_slice::<3, StoreBgr>(ops, buf, options)?,
+        Bgra => decode_slice::<4, StoreBgra>(ops, buf, options)?,
+        Argb => decode_slice::<4, StoreArgb>(ops, buf, options)?,
+        Abgr => decode_slice::<4, StoreAbgr>(ops, buf, options)?,
+        L => decode_slice::<1, StoreL>(ops, buf, options)?,
+        La => decode_slice::<2, StoreLa>(ops, buf, options)?,
+    };
+    if options.strict && decoded == desc.width * desc.height {
+        check_end(&ops[len..])?;
+    }
+    let decoded_len = decoded * layout.bytes_per_pixel();
+    qoi_convert_colorspace(&mut buf[..decoded_len], layout, colorspace, desc.colorspace)?;
+    Ok((desc, decoded))
+}
+
+/// Pixel layout as a type, so `decode_slice` is compiled separately for each layout.
+trait Store {
+    const LAYOUT: PixelLayout;
+}
+
+macro_rules! layout_stores {
+    ($($name:ident => $layout:expr,)*) => {$(
+        struct $name;
+
+        impl Store for $name {
+            const LAYOUT: PixelLayout = $layout;
+        }
+    )*};
+}
+
+layout_stores! {
+    StoreRgb => PixelLayout::Rgb,
+    StoreRgba => PixelLayout::Rgba,
+    StoreBgr => PixelLayout::Bgr,
+    StoreBgra => PixelLayout::Bgra,
+    StoreArgb => PixelLayout::Argb,
+    StoreAbgr => PixelLayout::Abgr,
+    StoreL => PixelLayout::L,
+    StoreLa => PixelLayout::La,
+}
+
+/// Decode ops from `data` into pixels of `N` bytes in layout of `S` and return length of ops
+/// and number of decoded pixels, which is less than size of image if `data` ends too early.
+/// In strict mode of `options` run going past the last pixel is an error.
+///
+/// Every op is read from fixed 5 byte window so there is one bounds check per op.
+/// Near the end of `data` window is padded with zeros and only there op is checked
+/// to not end past `data`. Current pixel is kept in local, not in [`DecodeState`],
+/// so it can stay in register.
+fn decode_slice<const N: usize, S: Store>(
+    data: &[u8],
+    pixels: &mut [u8],
+    options: &DecodeOptions,
+) -> Result<(usize, usize), QoiError> {
+    let total = pixels.len() / N;
+    let DecodeState {
+        mut index,
+        mut pixel,
+    } = DecodeState::new();
+
+    let mut pos = 0;
+    let mut out = pixels.chunks_exact_mut(N);
+    while let Some(first) = out.next() {
+        let (len, run) = match data.get(pos..pos + 5) {
+            // op can't end past data while whole window fits into it
+            Some(window) => {
+                let (next, len, run) = next_pixel(&mut index, pixel, window.try_into().unwrap());
+                pixel = next;
+                (len, run)
+            }
+            None => {
+                let mut window = [0; 5];
+                let rest = data.get(pos..).unwrap_or_default();
+                window[..rest.len()].copy_from_slice(rest);
+                let (next, len, run) = next_pixel(&mut index, pixel, &window);
+                pixel = next;
+                if pos + len > data.len() {
+                    return Ok((pos, total - out.len() - 1));
+                }
+                (len, run)
+            }
+        };
+        pos += len;
+
+        let bytes = match S::LAYOUT {
+            PixelLayout::Rgb | PixelLayout::Rgba => {
+                let QoiRGBA { r, g, b, a } = pixel;
+                [r, g, b, a]
+            }
+            layout => layout.write(pixel, options.gray).ok_or(QoiError::NotGray)?,
+        };
+        first.copy_from_slice(&bytes[..N]);
+        if run > 0 {
+            if options.strict && run as usize > out.len() {
+                return Err(QoiError::RunOverflow);
+            }
+            for out in out.by_ref().take(run as usize) {
+                out.copy_from_slice(&bytes[..N]);
+            }
+        }
+    }
+    Ok((pos, total))
+}
+
+/// Check that `rest` of stream after the last op is exactly `QOI_PADDING`.
+fn check_end(rest: &[u8]) -> Result<(), QoiError> {
+    match rest.get(..QOI_PADDING_SIZE) {
+        Some(padding) if padding == QOI_PADDING => {}
+        _ => return Err(QoiError::BadPadding),
+    }
+    if rest.len() > QOI_PADDING_SIZE {
+        return Err(QoiError::TrailingData);
+    }
+    Ok(())
+}
+
+/// State of decoder between ops.
+struct DecodeState {
+    index: [QoiRGBA; 64],
+    pixel: QoiRGBA,
+}
+
+impl DecodeState {
+    fn new() -> Self {
+        Self {
+            index: [QoiRGBA::new(0, 0, 0, 0); 64],
+            pixel: QoiRGBA::new(0, 0, 0, 255),
+        }
+    }
+
+    /// Apply op at start of `window` to current pixel, see [`next_pixel`].
+    ///
+    /// Returns length of op and number of times pixel is repeated after this one.
+    #[cfg(feature = "std")]
+    #[inline(always)]
+    fn apply_op(&mut self, window: &[u8; 5]) -> (usize, u8) {
+        let (pixel, len, run) = next_pixel(&mut self.index, self.pixel, window);
+        self.pixel = pixel;
+        (len, run)
+    }
+}
+
+/// Apply op at start of `window` to `pixel` and store result in `index`.
+///
+/// Returns new pixel, length of op and number of times pixel is repeated after this one.
+#[inline(always)]
+fn next_pixel(
+    index: &mut [QoiRGBA; 64],
+    mut pixel: QoiRGBA,
+    window: &[u8; 5],
+) -> (QoiRGBA, usize, u8) {
+    let op_byte = window[0];
+    let mut run = 0;
+    let mut len = 1;
+
+    match op_byte {
+        QOI_OP_RGB => {
+            pixel.r = window[1];
+            pixel.g = window[2];
+            pixel.b = window[3];
+            len = 4;
+        }
+        QOI_OP_RGBA => {
+            pixel = QoiRGBA::new(window[1], window[2], window[3], window[4]);
+            len = 5;
+        }
+        0x00..=0x3f => {
+            pixel = index[op_byte as usize];
+        }
+        0x40..=0x7f => {
+            pixel.r = pixel.r.wrapping_add((op_byte >> 4) & 0x03).wrapping_sub(2);
+            pixel.g = pixel.g.wrapping_add((op_byte >> 2) & 0x03).wrapping_sub(2);
+            pixel.b = pixel.b.wrapping_add(op_byte & 0x03).wrapping_sub(2);
+        }
+        0x80..=0xbf => {
+            let delta_byte = window[1];
+            let dg = (op_byte & 0x3f).wrapping_sub(32);
+
+            pixel.r = pixel
+                .r
+                .wrapping_add(dg.wrapping_sub(8).wrapping_add(delta_byte >> 4));
+            pixel.g = pixel.g.wrapping_add(dg);
+            pixel.b = pixel
+                .b
+                .wrapping_add(dg.wrapping_sub(8).wrapping_add(delta_byte & 0x0f));
+            len = 2;
+        }
+        _ => {
+            run = op_byte & 0x3f;
+        }
+    }
+
+    index[color_hash(pixel) % 64] = pixel;
+    (pixel, len, run)
+}
+
+/// Incremental QOI decoder reading from `impl Read`.
+///
+/// Header is parsed on construction and available through
+/// [`desc`](struct.QoiDecoder.html#method.desc), pixels are then decoded row by row
+/// with [`read_rows`](struct.QoiDecoder.html#method.read_rows) or
+/// [`scanlines`](struct.QoiDecoder.html#method.scanlines), so the whole image never
+/// has to be in memory.
+///
+/// ### Example of decoding image row by row:
+/// ```
+/// use std::fs::File;
+/// use std::io::BufReader;
+/// use qoi::*;
+///
+/// let file = BufReader::new(File::open("qoi_test_images/wikipedia_008.qoi").unwrap());
+/// let mut decoder = QoiDecoder::new(file, None).unwrap();
+/// let width = decoder.desc().width;
+/// for row in decoder.scanlines() {
+///     let row = row.unwrap();
+///     assert_eq!(row.len(), width * 3);
+/// }
+/// ```
+#[cfg(feature = "std")]
+pub struct QoiDecoder<R: Read> {
+    reader: R,
+    desc: QoiDescriptor,
+    layout: PixelLayout,
+    state: DecodeState,
+    run: u8,
+    /// Rows left to decode.
+    rows_left: usize,
+    strict: bool,
+    gray: GrayMode,
+    /// Colorspace of image, pixels are converted from it to colorspace of `desc`.
+    colorspace: Colorspace,
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> QoiDecoder<R> {
+    /// Read and validate header of QOI image.
+    ///
+    /// Will take `ChanelMode` form descriptor of file if not provided, overwise will use provided.
+    pub fn new(reader: R, channels: Option<ChanelMode>) -> Result<Self, QoiError> {
+        let options = DecodeOptions {
+            layout: channels.map(PixelLayout::from),
+            ..DecodeOptions::default()
+        };
+        Self::with_options(reader, &options)
+    }
+
+    /// Read and validate header of QOI image, pixels will be decoded in `layout`.
+    pub fn with_layout(reader: R, layout: PixelLayout) -> Result<Self, QoiError> {
+        let options = DecodeOptions {
+            layout: Some(layout),
+            ..DecodeOptions::default()
+        };
+        Self::with_options(reader, &options)
+    }
+
+    /// Read and validate header of QOI image, pixels will be decoded with `options`.
+    ///
+    /// In strict mode end of stream is checked after the last row is decoded.
+    pub fn with_options(mut reader: R, options: &DecodeOptions) -> Result<Self, QoiError> {
+        let desc = read_header(&mut reader, options.limits.header_max_pixels())?;
+        let (desc, layout, colorspace) = resolve_header(desc, options)?;
+
+        Ok(Self {
+            reader,
+            rows_left: desc.height,
+            layout,
+            desc,
+            state: DecodeState::new(),
+            run: 0,
+            strict: options.strict,
+            gray: options.gray,
+            colorspace,
+        })
+    }
+
+    /// Descriptor of image, `channels` is the mode pixels are decoded in.
+    pub fn desc(&self) -> &QoiDescriptor {
+        &self.desc
+    }
+
+    /// Number of rows not yet decoded.
+    pub fn rows_left(&self) -> usize {
+        self.rows_left
+    }
+
+    /// Fill `buf` with next rows of pixels and return number of decoded rows.
+    ///
+    /// Length of `buf` must be a multiple of row length, returns less rows
+    /// than fit into `buf` only at the end of image.
+    pub fn read_rows(&mut self, buf: &mut [u8]) -> Result<usize, QoiError> {
+        let row_len = self.row_len();
+        if !buf.len().is_multiple_of(row_len) {
+            return Err(QoiError::BufferSizeMismatch {
+                expected: buf.len() - buf.len() % row_len,
+                actual: buf.len(),
+            });
+        }
+
+        let rows = (buf.len() / row_len).min(self.rows_left);
+        self.decode_pixels(&mut buf[..rows * row_len])?;
+        self.finish_rows(rows)?;
+        Ok(rows)
+    }
+
+    /// Fill `buf` of whole pixels with next pixels, regardless of where rows end.
+    fn decode_pixels(&mut self, buf: &mut [u8]) -> Result<(), QoiError> {
+        let pixel_size = self.layout.bytes_per_pixel();
+        for pixel in buf.chunks_exact_mut(pixel_size) {
+            let bytes = self
+                .layout
+                .write(self.decode_pixel()?, self.gray)
+                .ok_or(QoiError::NotGray)?;
+            pixel.copy_from_slice(&bytes[..pixel_size]);
+        }
+        qoi_convert_colorspace(buf, self.layout, self.colorspace, self.desc.colorspace)
+    }
+
+    /// Mark `rows` as decoded, in strict mode end of stream is checked after the last one.
+    fn finish_rows(&mut self, rows: usize) -> Result<(), QoiError> {
+        self.rows_left -= rows;
+        if self.strict && rows > 0 && self.rows_left == 0 {
+            self.check_end()?;
+        }
+        Ok(())
+    }
+
+    /// Check that run ends with the last pixel and stream ends with `QOI_PADDING`.
+    fn check_end(&mut self) -> Result<(), QoiError> {
+        if self.run > 0 {
+            return Err(QoiError::RunOverflow);
+        }
+        let mut padding = [0; QOI_PADDING_SIZE];
+        match self.reader.read_exact(&mut padding) {
+            Ok(()) if padding == QOI_PADDING => {}
+            Ok(()) => return Err(QoiError::BadPadding),
+            Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => {
+                return Err(QoiError::BadPadding)
+            }
+            Err(err) => return Err(err.into()),
+        }
+        if self.reader.read(&mut [0])? > 0 {
+            return Err(QoiError::TrailingData);
+        }
+        Ok(())
+    }
+
+    /// Length of row of pixels in bytes.
+    fn row_len(&self) -> usize {
+        self.desc.width * self.layout.bytes_per_pixel()
+    }
+
+    /// Iterator over remaining rows of image.
+    pub fn scanlines(&mut self) -> Scanlines<'_, R> {
+        Scanlines {
+            decoder: self,
+            failed: false,
+        }
+    }
+
+    fn decode_pixel(&mut self) -> Result<QoiRGBA, QoiError> {
+        if self.run > 0 {
+            self.run -= 1;
+            return Ok(self.state.pixel);
+        }
+
+        let mut window = [0u8; 5];
+        read_stream(&mut self.reader, &mut window[..1])?;
+        let len = op_len(window[0]);
+        read_stream(&mut self.reader, &mut window[1..len])?;
+        (_, self.run) = self.state.apply_op(&window);
+        Ok(self.state.pixel)
+    }
+}
+
+/// Iterator over rows of [`QoiDecoder`](struct.QoiDecoder.html).
+///
+/// Stops after the last row or after first error.
+#[cfg(feature = "std")]
+pub struct Scanlines<'a, R: Read> {
+    decoder: &'a mut QoiDecoder<R>,
+    failed: bool,
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> Iterator for Scanlines<'_, R> {
+    type Item = Result<Vec<u8>, QoiError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.decoder.rows_left == 0 {
+            return None;
+        }
+        let mut row = vec![0; self.decoder.row_len()];
+        match self.decoder.read_rows(&mut row) {
+            Ok(_) => Some(Ok(row)),
+            Err(err) => {
+                self.failed = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
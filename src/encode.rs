@@ -0,0 +1,684 @@
+//! QOI encoder.
+use super::*;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io::Write;
+
+/// Header of QOI image, descriptor must be validated with `pixel_count`.
+fn header(desc: &QoiDescriptor) -> [u8; QOI_HEADER_SIZE] {
+    let mut header = [0; QOI_HEADER_SIZE];
+    header[..4].copy_from_slice(b"qoif");
+    header[4..8].copy_from_slice(&(desc.width as u32).to_be_bytes());
+    header[8..12].copy_from_slice(&(desc.height as u32).to_be_bytes());
+    header[12] = desc.channels as u8;
+    header[13] = desc.colorspace as u8;
+    header
+}
+
+/// Options of encoding, `Default` gives the same behaviour as [`qoi_encode`](fn.qoi_encode.html).
+///
+/// ### Example of encoding image bigger than `QOI_PIXELS_MAX` row by row:
+/// ```no_run
+/// use std::{fs::File, io::BufWriter};
+/// use qoi::*;
+///
+/// let desc = QoiDescriptor {
+///     width: 40_000,
+///     height: 20_000,
+///     channels: ChanelMode::Rgb,
+///     colorspace: Colorspace::Srgb,
+/// };
+/// let options = EncodeOptions {
+///     max_pixels: usize::MAX,
+///     ..EncodeOptions::default()
+/// };
+/// let file = BufWriter::new(File::create("panorama.qoi").unwrap());
+/// let mut encoder = QoiEncoder::with_options(file, &desc, &options).unwrap();
+/// for _ in 0..desc.height {
+///     encoder.write_row(&[128; 40_000 * 3]).unwrap();
+/// }
+/// encoder.finish().unwrap();
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct EncodeOptions {
+    /// Layout of pixels, RGB or RGBA by channels of descriptor when `None`.
+    pub layout: Option<PixelLayout>,
+    /// Maximum of `width * height`, one less than `QOI_PIXELS_MAX` by default.
+    ///
+    /// Trusted callers can raise it or remove it with `usize::MAX` on 64-bit targets.
+    pub max_pixels: usize,
+}
+
+impl Default for EncodeOptions {
+    fn default() -> Self {
+        Self {
+            layout: None,
+            max_pixels: DEFAULT_MAX_PIXELS,
+        }
+    }
+}
+
+impl EncodeOptions {
+    /// Options with `layout` and default limit.
+    fn with_layout(layout: PixelLayout) -> Self {
+        Self {
+            layout: Some(layout),
+            ..Self::default()
+        }
+    }
+
+    /// Layout of pixels of image described by `desc`, must have the same channels.
+    fn layout(&self, desc: &QoiDescriptor) -> Result<PixelLayout, QoiError> {
+        let layout = self.layout.unwrap_or(desc.channels.into());
+        if layout.channels() != desc.channels {
+            return Err(QoiError::LayoutMismatch {
+                channels: desc.channels,
+                layout,
+            });
+        }
+        Ok(layout)
+    }
+}
+
+/// Check that `pixels` holds exactly the image described by `desc` and return its layout.
+fn check_pixels(
+    pixels: &[u8],
+    desc: &QoiDescriptor,
+    options: &EncodeOptions,
+) -> Result<PixelLayout, QoiError> {
+    let layout = options.layout(desc)?;
+    let expected = pixel_count(desc, options.max_pixels)?
+        .checked_mul(layout.bytes_per_pixel())
+        .ok_or(QoiError::TooManyPixels)?;
+    if pixels.len() != expected {
+        return Err(QoiError::BufferSizeMismatch {
+            expected,
+            actual: pixels.len(),
+        });
+    }
+    Ok(layout)
+}
+
+/// Encode every pixel of `pixels` stored in `layout`.
+///
+/// Each layout gets its own copy of the loop so swizzling is resolved at compile time,
+/// RGB pixels are expanded to RGBA in blocks first.
+fn encode_pixels(
+    pixels: &[u8],
+    layout: PixelLayout,
+    state: &mut EncodeState,
+    put: &mut impl FnMut(&[u8]) -> Result<(), QoiError>,
+) -> Result<(), QoiError> {
+    use PixelLayout::*;
+    match layout {
+        Rgb => {
+            let mut rgba = [[0; 4]; 256];
+            for block in pixels.chunks(rgba.len() * 3) {
+                let rgba = &mut rgba[..block.len() / 3];
+                simd::rgb_to_rgba(block, rgba);
+                encode_chunks(rgba.as_flattened(), 4, state, put, |pixel| Rgba.read(pixel))?;
+            }
+            Ok(())
+        }
+        Rgba => encode_chunks(pixels, 4, state, put, |pixel| Rgba.read(pixel)),
+        Bgr => encode_chunks(pixels, 3, state, put, |pixel| Bgr.read(pixel)),
+        Bgra => encode_chunks(pixels, 4, state, put, |pixel| Bgra.read(pixel)),
+        Argb => encode_chunks(pixels, 4, state, put, |pixel| Argb.read(pixel)),
+        Abgr => encode_chunks(pixels, 4, state, put, |pixel| Abgr.read(pixel)),
+        L => encode_chunks(pixels, 1, state, put, |pixel| L.read(pixel)),
+        La => encode_chunks(pixels, 2, state, put, |pixel| La.read(pixel)),
+    }
+}
+
+/// Encode pixels of `pixel_size` bytes, run of equal pixels is found by comparing bytes in bulk.
+#[inline(always)]
+fn encode_chunks(
+    pixels: &[u8],
+    pixel_size: usize,
+    state: &mut EncodeState,
+    put: &mut impl FnMut(&[u8]) -> Result<(), QoiError>,
+    read: impl Fn(&[u8]) -> QoiRGBA,
+) -> Result<(), QoiError> {
+    let mut pos = 0;
+    while pos < pixels.len() {
+        let bytes = &pixels[pos..pos + pixel_size];
+        let pixel = read(bytes);
+        if pixel == state.pixel_previous {
+            // short runs are common in photos, only longer ones are worth scanning in bulk
+            let mut run = 1;
+            let mut next = pos + pixel_size;
+            while run < 4 && pixels.get(next..next + pixel_size) == Some(bytes) {
+                run += 1;
+                next += pixel_size;
+            }
+            if run == 4 {
+                run += simd::run_length(&pixels[next..], bytes);
+            }
+            state.extend_run(run, put)?;
+            pos += run * pixel_size;
+        } else {
+            state.encode_pixel(pixel, put)?;
+            pos += pixel_size;
+        }
+    }
+    Ok(())
+}
+
+/// Worst case size of QOI image described by `desc`, including header and padding.
+///
+/// Buffer of this size is always enough for [`qoi_encode_into`](fn.qoi_encode_into.html).
+pub fn qoi_max_encoded_size(desc: &QoiDescriptor) -> Result<usize, QoiError> {
+    max_encoded_size(desc, DEFAULT_MAX_PIXELS)
+}
+
+fn max_encoded_size(desc: &QoiDescriptor, max_pixels: usize) -> Result<usize, QoiError> {
+    pixel_count(desc, max_pixels)?
+        .checked_mul(desc.channels as usize + 1)
+        .and_then(|size| size.checked_add(QOI_HEADER_SIZE + QOI_PADDING_SIZE))
+        .ok_or(QoiError::TooManyPixels)
+}
+
+/// Encode raw RGB or RGBA pixels into caller-provided `out` and return number of written bytes.
+///
+/// Doesn't allocate, fails if `out` is too small for encoded image,
+/// [`qoi_max_encoded_size`](fn.qoi_max_encoded_size.html) is always enough.
+pub fn qoi_encode_into(
+    pixels: &[u8],
+    desc: &QoiDescriptor,
+    out: &mut [u8],
+) -> Result<usize, QoiError> {
+    qoi_encode_into_with_layout(pixels, desc, desc.channels.into(), out)
+}
+
+/// Same as [`qoi_encode_into`](fn.qoi_encode_into.html) but `pixels` are stored in `layout`.
+///
+/// `layout` must have the same number of channels as `desc`.
+pub fn qoi_encode_into_with_layout(
+    pixels: &[u8],
+    desc: &QoiDescriptor,
+    layout: PixelLayout,
+    out: &mut [u8],
+) -> Result<usize, QoiError> {
+    qoi_encode_into_with_options(pixels, desc, &EncodeOptions::with_layout(layout), out)
+}
+
+/// Same as [`qoi_encode_into`](fn.qoi_encode_into.html) but with `options`.
+pub fn qoi_encode_into_with_options(
+    pixels: &[u8],
+    desc: &QoiDescriptor,
+    options: &EncodeOptions,
+    out: &mut [u8],
+) -> Result<usize, QoiError> {
+    let layout = check_pixels(pixels, desc, options)?;
+
+    let max_size = max_encoded_size(desc, options.max_pixels)?;
+    let out_len = out.len();
+    let mut pos = 0;
+    let mut put = |bytes: &[u8]| {
+        let dst = out
+            .get_mut(pos..pos + bytes.len())
+            .ok_or(QoiError::BufferSizeMismatch {
+                expected: max_size,
+                actual: out_len,
+            })?;
+        dst.copy_from_slice(bytes);
+        pos += bytes.len();
+        Ok(())
+    };
+
+    put(&header(desc))?;
+    let mut state = EncodeState::new(desc.width * desc.height);
+    encode_pixels(pixels, layout, &mut state, &mut put)?;
+    state.finish(&mut put)?;
+    put(&QOI_PADDING)?;
+    Ok(pos)
+}
+
+/// Encode raw RGB or RGBA pixels into a QOI image in memory.
+#[cfg(feature = "alloc")]
+pub fn qoi_encode(pixels: &[u8], desc: &QoiDescriptor) -> Result<Vec<u8>, QoiError> {
+    qoi_encode_with_layout(pixels, desc, desc.channels.into())
+}
+
+/// Encode pixels stored in `layout`, e.g. BGRA frame of screen capture, into a QOI image in memory.
+///
+/// `layout` must have the same number of channels as `desc`.
+///
+/// ### Example of encoding BGRA pixels:
+/// ```
+/// use qoi::*;
+///
+/// let bgra = [255, 0, 0, 255, 0, 0, 255, 128];
+/// let desc = QoiDescriptor {
+///     width: 2,
+///     height: 1,
+///     channels: ChanelMode::Rgba,
+///     colorspace: Colorspace::Srgb,
+/// };
+/// let bytes = qoi_encode_with_layout(&bgra, &desc, PixelLayout::Bgra).unwrap();
+/// let (rgba, _) = qoi_decode_from_slice(&bytes, None).unwrap();
+/// assert_eq!(rgba, [0, 0, 255, 255, 255, 0, 0, 128]);
+/// ```
+#[cfg(feature = "alloc")]
+pub fn qoi_encode_with_layout(
+    pixels: &[u8],
+    desc: &QoiDescriptor,
+    layout: PixelLayout,
+) -> Result<Vec<u8>, QoiError> {
+    qoi_encode_with_options(pixels, desc, &EncodeOptions::with_layout(layout))
+}
+
+/// Encode pixels into a QOI image in memory with `options`.
+#[cfg(feature = "alloc")]
+pub fn qoi_encode_with_options(
+    pixels: &[u8],
+    desc: &QoiDescriptor,
+    options: &EncodeOptions,
+) -> Result<Vec<u8>, QoiError> {
+    let layout = check_pixels(pixels, desc, options)?;
+
+    let mut bytes = Vec::with_capacity(max_encoded_size(desc, options.max_pixels)?);
+    let mut put = |op: &[u8]| {
+        bytes.extend_from_slice(op);
+        Ok(())
+    };
+
+    put(&header(desc))?;
+    let mut state = EncodeState::new(desc.width * desc.height);
+    encode_pixels(pixels, layout, &mut state, &mut put)?;
+    state.finish(&mut put)?;
+    put(&QOI_PADDING)?;
+    Ok(bytes)
+}
+
+/// Encode pixels in horizontal bands on rayon threads.
+///
+/// Every band except first is encoded with fresh state and starts with `QOI_OP_RGBA`,
+/// so the result is a valid QOI stream slightly larger than output of
+/// [`qoi_encode`](fn.qoi_encode.html), which it matches for images smaller than one band.
+///
+/// ### Example of encoding big image:
+/// ```
+/// use qoi::*;
+///
+/// let desc = QoiDescriptor {
+///     width: 2048,
+///     height: 2048,
+///     channels: ChanelMode::Rgb,
+///     colorspace: Colorspace::Srgb,
+/// };
+/// let pixels: Vec<u8> = (0..2048 * 2048 * 3).map(|i| (i / 4096) as u8).collect();
+/// let bytes = qoi_encode_parallel(&pixels, &desc).unwrap();
+/// assert_eq!(qoi_decode_from_slice(&bytes, None).unwrap().0, pixels);
+/// ```
+#[cfg(feature = "parallel")]
+pub fn qoi_encode_parallel(pixels: &[u8], desc: &QoiDescriptor) -> Result<Vec<u8>, QoiError> {
+    qoi_encode_parallel_with_layout(pixels, desc, desc.channels.into())
+}
+
+/// Same as [`qoi_encode_parallel`](fn.qoi_encode_parallel.html) but `pixels` are stored in `layout`.
+#[cfg(feature = "parallel")]
+pub fn qoi_encode_parallel_with_layout(
+    pixels: &[u8],
+    desc: &QoiDescriptor,
+    layout: PixelLayout,
+) -> Result<Vec<u8>, QoiError> {
+    qoi_encode_parallel_with_options(pixels, desc, &EncodeOptions::with_layout(layout))
+}
+
+/// Same as [`qoi_encode_parallel`](fn.qoi_encode_parallel.html) but with `options`.
+#[cfg(feature = "parallel")]
+pub fn qoi_encode_parallel_with_options(
+    pixels: &[u8],
+    desc: &QoiDescriptor,
+    options: &EncodeOptions,
+) -> Result<Vec<u8>, QoiError> {
+    let layout = check_pixels(pixels, desc, options)?;
+    let band_rows = (PARALLEL_BAND_PIXELS / desc.width).max(1);
+    encode_bands(pixels, desc, layout, band_rows)
+}
+
+/// Number of pixels in one band of [`qoi_encode_parallel`](fn.qoi_encode_parallel.html).
+#[cfg(feature = "parallel")]
+const PARALLEL_BAND_PIXELS: usize = 1 << 18;
+
+/// Encode validated `pixels` in bands of `band_rows` rows.
+#[cfg(feature = "parallel")]
+pub(crate) fn encode_bands(
+    pixels: &[u8],
+    desc: &QoiDescriptor,
+    layout: PixelLayout,
+    band_rows: usize,
+) -> Result<Vec<u8>, QoiError> {
+    use rayon::prelude::*;
+
+    let pixel_size = layout.bytes_per_pixel();
+    let bands = pixels
+        .par_chunks(band_rows * desc.width * pixel_size)
+        .enumerate()
+        .map(|(i, band)| {
+            let mut bytes = Vec::with_capacity(band.len() / pixel_size * (pixel_size + 1));
+            let mut put = |op: &[u8]| {
+                bytes.extend_from_slice(op);
+                Ok(())
+            };
+            let pixel_count = band.len() / pixel_size;
+            if i == 0 {
+                let mut state = EncodeState::new(pixel_count);
+                encode_pixels(band, layout, &mut state, &mut put)?;
+                state.finish(&mut put)?;
+            } else {
+                let mut state = EncodeState::new_band(pixel_count);
+                let (first, rest) = band.split_at(pixel_size);
+                state.encode_rgba(layout.read(first), &mut put)?;
+                encode_pixels(rest, layout, &mut state, &mut put)?;
+                state.finish(&mut put)?;
+            }
+            Ok(bytes)
+        })
+        .collect::<Result<Vec<_>, QoiError>>()?;
+
+    let len = bands.iter().map(Vec::len).sum::<usize>() + QOI_HEADER_SIZE + QOI_PADDING_SIZE;
+    let mut bytes = Vec::with_capacity(len);
+    bytes.extend_from_slice(&header(desc));
+    for band in bands {
+        bytes.extend_from_slice(&band);
+    }
+    bytes.extend_from_slice(&QOI_PADDING);
+    Ok(bytes)
+}
+
+/// State of encoder between pixels, bytes of ops are passed to `put`.
+struct EncodeState {
+    index: [QoiRGBA; 64],
+    pixel_previous: QoiRGBA,
+    run: u8,
+    /// Pixels left to encode.
+    remaining: usize,
+}
+
+impl EncodeState {
+    fn new(pixel_count: usize) -> Self {
+        Self {
+            index: [QoiRGBA::new(0, 0, 0, 0); 64],
+            pixel_previous: QoiRGBA::new(0, 0, 0, 255),
+            run: 0,
+            remaining: pixel_count,
+        }
+    }
+
+    /// State for band of image which doesn't start at first pixel.
+    ///
+    /// Decoder's index is unknown at this point so no slot may match any pixel,
+    /// zeroed index matches transparent black in slot 0 so it gets a pixel hashed elsewhere.
+    #[cfg(feature = "parallel")]
+    fn new_band(pixel_count: usize) -> Self {
+        let mut state = Self::new(pixel_count);
+        state.index[0] = QoiRGBA::new(0, 0, 0, 1);
+        state
+    }
+
+    /// Encode `pixel` as `QOI_OP_RGBA` which doesn't depend on previous pixel.
+    #[cfg(feature = "parallel")]
+    fn encode_rgba(
+        &mut self,
+        pixel: QoiRGBA,
+        put: &mut impl FnMut(&[u8]) -> Result<(), QoiError>,
+    ) -> Result<(), QoiError> {
+        self.remaining -= 1;
+        self.pixel_previous = pixel;
+        self.index[color_hash(pixel) % 64] = pixel;
+        put(&[QOI_OP_RGBA, pixel.r, pixel.g, pixel.b, pixel.a])
+    }
+
+    /// Flush run that wasn't ended by last pixel.
+    fn finish(
+        &mut self,
+        put: &mut impl FnMut(&[u8]) -> Result<(), QoiError>,
+    ) -> Result<(), QoiError> {
+        if self.run > 0 {
+            put(&[QOI_OP_RUN | (self.run - 1)])?;
+            self.run = 0;
+        }
+        Ok(())
+    }
+
+    /// Add `count` pixels equal to previous one to the run.
+    ///
+    /// Same ops are written as if every pixel was passed to `encode_pixel`.
+    fn extend_run(
+        &mut self,
+        count: usize,
+        put: &mut impl FnMut(&[u8]) -> Result<(), QoiError>,
+    ) -> Result<(), QoiError> {
+        self.remaining -= count;
+        let mut run = self.run as usize + count;
+        while run >= 62 {
+            put(&[QOI_OP_RUN | 61])?;
+            run -= 62;
+        }
+        self.run = run as u8;
+        if self.remaining == 0 {
+            self.finish(put)?;
+        }
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn encode_pixel(
+        &mut self,
+        pixel: QoiRGBA,
+        put: &mut impl FnMut(&[u8]) -> Result<(), QoiError>,
+    ) -> Result<(), QoiError> {
+        self.remaining -= 1;
+        let pixel_previous = self.pixel_previous;
+        self.pixel_previous = pixel;
+
+        if pixel == pixel_previous {
+            self.run += 1;
+            if self.run == 62 || self.remaining == 0 {
+                put(&[QOI_OP_RUN | (self.run - 1)])?;
+                self.run = 0;
+            }
+            return Ok(());
+        }
+
+        if self.run > 0 {
+            put(&[QOI_OP_RUN | (self.run - 1)])?;
+            self.run = 0;
+        }
+
+        let index_pos = color_hash(pixel) % 64;
+
+        if self.index[index_pos] == pixel {
+            return put(&[QOI_OP_INDEX | index_pos as u8]);
+        }
+        self.index[index_pos] = pixel;
+
+        if pixel.a == pixel_previous.a {
+            let dr = pixel.r.wrapping_sub(pixel_previous.r) as i8;
+            let dg = pixel.g.wrapping_sub(pixel_previous.g) as i8;
+            let db = pixel.b.wrapping_sub(pixel_previous.b) as i8;
+
+            let dg_dr = dr.wrapping_sub(dg);
+            let dg_db = db.wrapping_sub(dg);
+
+            if (-2..=1).contains(&dr) && (-2..=1).contains(&dg) && (-2..=1).contains(&db) {
+                put(&[QOI_OP_DIFF
+                    | ((dr + 2) as u8) << 4
+                    | ((dg + 2) as u8) << 2
+                    | ((db + 2) as u8)])
+            } else if (-8..=7).contains(&dg_dr)
+                && (-8..=7).contains(&dg_db)
+                && (-32..=31).contains(&dg)
+            {
+                put(&[
+                    QOI_OP_LUMA | ((dg + 32) as u8),
+                    ((dg_dr + 8) as u8) << 4 | ((dg_db + 8) as u8),
+                ])
+            } else {
+                put(&[QOI_OP_RGB, pixel.r, pixel.g, pixel.b])
+            }
+        } else {
+            put(&[QOI_OP_RGBA, pixel.r, pixel.g, pixel.b, pixel.a])
+        }
+    }
+}
+
+/// Streaming QOI encoder writing directly to `impl Write`.
+///
+/// Header is written on construction, pixels may be supplied row by row with
+/// [`write_row`](struct.QoiEncoder.html#method.write_row) or in chunks of any size
+/// with [`write_pixels`](struct.QoiEncoder.html#method.write_pixels).
+/// [`finish`](struct.QoiEncoder.html#method.finish) must be called after the last pixel.
+/// Every op is written separately so you should use `BufWriter` for files.
+///
+/// ### Example of encoding image row by row:
+/// ```
+/// use std::fs::File;
+/// use std::io::BufWriter;
+/// use qoi::*;
+///
+/// let desc = QoiDescriptor {
+///     width: 4,
+///     height: 2,
+///     channels: ChanelMode::Rgb,
+///     colorspace: Colorspace::Srgb,
+/// };
+/// let file = BufWriter::new(File::create(std::env::temp_dir().join("rows.qoi")).unwrap());
+/// let mut encoder = QoiEncoder::new(file, &desc).unwrap();
+/// for row in [[255; 12], [0; 12]] {
+///     encoder.write_row(&row).unwrap();
+/// }
+/// encoder.finish().unwrap();
+/// ```
+#[cfg(feature = "std")]
+pub struct QoiEncoder<W: Write> {
+    writer: W,
+    desc: QoiDescriptor,
+    layout: PixelLayout,
+    state: EncodeState,
+    /// Size of all pixels of image in bytes.
+    len: usize,
+    /// Bytes of pixels written so far.
+    written: usize,
+    /// Bytes of pixel split between two chunks.
+    partial: [u8; 4],
+    partial_len: usize,
+}
+
+#[cfg(feature = "std")]
+impl<W: Write> QoiEncoder<W> {
+    /// Validate descriptor and write header of QOI image to `writer`.
+    pub fn new(writer: W, desc: &QoiDescriptor) -> Result<Self, QoiError> {
+        Self::with_layout(writer, desc, desc.channels.into())
+    }
+
+    /// Same as [`new`](struct.QoiEncoder.html#method.new) but pixels will be supplied in `layout`.
+    ///
+    /// `layout` must have the same number of channels as `desc`.
+    pub fn with_layout(
+        writer: W,
+        desc: &QoiDescriptor,
+        layout: PixelLayout,
+    ) -> Result<Self, QoiError> {
+        Self::with_options(writer, desc, &EncodeOptions::with_layout(layout))
+    }
+
+    /// Same as [`new`](struct.QoiEncoder.html#method.new) but with `options`.
+    ///
+    /// Only one row has to be in memory at a time, so with raised `max_pixels`
+    /// this is the way to encode images too big to keep whole.
+    pub fn with_options(
+        mut writer: W,
+        desc: &QoiDescriptor,
+        options: &EncodeOptions,
+    ) -> Result<Self, QoiError> {
+        let layout = options.layout(desc)?;
+        let pixel_count = pixel_count(desc, options.max_pixels)?;
+        let len = pixel_count
+            .checked_mul(layout.bytes_per_pixel())
+            .ok_or(QoiError::TooManyPixels)?;
+        writer.write_all(&header(desc))?;
+
+        Ok(Self {
+            writer,
+            desc: desc.clone(),
+            layout,
+            state: EncodeState::new(pixel_count),
+            len,
+            written: 0,
+            partial: [0; 4],
+            partial_len: 0,
+        })
+    }
+
+    /// Encode one row of `width` pixels.
+    pub fn write_row(&mut self, row: &[u8]) -> Result<(), QoiError> {
+        let expected = self.desc.width * self.layout.bytes_per_pixel();
+        if row.len() != expected {
+            return Err(QoiError::BufferSizeMismatch {
+                expected,
+                actual: row.len(),
+            });
+        }
+        self.write_pixels(row)
+    }
+
+    /// Encode next chunk of flat pixels, chunk may end in the middle of a pixel.
+    pub fn write_pixels(&mut self, mut pixels: &[u8]) -> Result<(), QoiError> {
+        let pixel_size = self.layout.bytes_per_pixel();
+        let expected = self.len - self.written;
+        if pixels.len() > expected {
+            return Err(QoiError::BufferSizeMismatch {
+                expected,
+                actual: pixels.len(),
+            });
+        }
+        self.written += pixels.len();
+
+        let writer = &mut self.writer;
+        let mut put = |op: &[u8]| writer.write_all(op).map_err(QoiError::from);
+
+        if self.partial_len > 0 {
+            let take = (pixel_size - self.partial_len).min(pixels.len());
+            self.partial[self.partial_len..self.partial_len + take]
+                .copy_from_slice(&pixels[..take]);
+            self.partial_len += take;
+            pixels = &pixels[take..];
+            if self.partial_len < pixel_size {
+                return Ok(());
+            }
+            self.partial_len = 0;
+            let pixel = self.layout.read(&self.partial[..pixel_size]);
+            self.state.encode_pixel(pixel, &mut put)?;
+        }
+
+        let whole = pixels.len() - pixels.len() % pixel_size;
+        encode_pixels(&pixels[..whole], self.layout, &mut self.state, &mut put)?;
+
+        let rest = &pixels[whole..];
+        self.partial[..rest.len()].copy_from_slice(rest);
+        self.partial_len = rest.len();
+        Ok(())
+    }
+
+    /// Write end of stream and return underlying writer.
+    ///
+    /// Fails if not every pixel from descriptor was written.
+    pub fn finish(mut self) -> Result<W, QoiError> {
+        if self.written < self.len {
+            return Err(QoiError::BufferSizeMismatch {
+                expected: self.len,
+                actual: self.written,
+            });
+        }
+        let writer = &mut self.writer;
+        self.state
+            .finish(&mut |op: &[u8]| writer.write_all(op).map_err(QoiError::from))?;
+        self.writer.write_all(&QOI_PADDING)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
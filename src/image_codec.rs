@@ -0,0 +1,266 @@
+//! Integration with `image` crate.
+use super::*;
+use ::image::{
+    error::{
+        DecodingError, EncodingError, ImageFormatHint, LimitError, LimitErrorKind,
+        UnsupportedError, UnsupportedErrorKind,
+    },
+    io::{LimitSupport, Limits},
+    ColorType, DynamicImage, ImageDecoder, ImageEncoder, ImageError, ImageResult, RgbImage,
+    RgbaImage,
+};
+use std::{
+    borrow::Cow,
+    io::{Cursor, Read, Write},
+};
+
+fn format_hint() -> ImageFormatHint {
+    ImageFormatHint::Name("QOI".to_string())
+}
+
+fn decoding_error(err: QoiError) -> ImageError {
+    match err {
+        QoiError::Io(err) => ImageError::IoError(err),
+        QoiError::LimitsExceeded => {
+            ImageError::Limits(LimitError::from_kind(LimitErrorKind::DimensionError))
+        }
+        err => ImageError::Decoding(DecodingError::new(format_hint(), err)),
+    }
+}
+
+fn encoding_error(err: QoiError) -> ImageError {
+    match err {
+        QoiError::Io(err) => ImageError::IoError(err),
+        err => ImageError::Encoding(EncodingError::new(format_hint(), err)),
+    }
+}
+
+/// QOI decoder implementing `image::ImageDecoder`, pixels are RGB8 or RGBA8 by channels of file.
+///
+/// ### Example of loading `DynamicImage`:
+/// ```
+/// use std::{fs::File, io::BufReader};
+/// use image::DynamicImage;
+/// use qoi::*;
+///
+/// let file = BufReader::new(File::open("qoi_test_images/wikipedia_008.qoi").unwrap());
+/// let image = DynamicImage::from_decoder(QoiImageDecoder::new(file).unwrap()).unwrap();
+/// assert!(matches!(image, DynamicImage::ImageRgb8(_)));
+/// ```
+pub struct QoiImageDecoder<R: Read> {
+    decoder: QoiDecoder<R>,
+}
+
+impl<R: Read> QoiImageDecoder<R> {
+    /// Read and validate header of QOI image.
+    pub fn new(reader: R) -> ImageResult<Self> {
+        Self::with_limits(reader, DecodeLimits::default())
+    }
+
+    /// Read header of QOI image and check it against `limits`.
+    pub fn with_limits(reader: R, limits: DecodeLimits) -> ImageResult<Self> {
+        let options = DecodeOptions {
+            limits,
+            ..DecodeOptions::default()
+        };
+        let decoder = QoiDecoder::with_options(reader, &options).map_err(decoding_error)?;
+        Ok(Self { decoder })
+    }
+}
+
+impl<'a, R: Read + 'a> ImageDecoder<'a> for QoiImageDecoder<R> {
+    type Reader = Cursor<Vec<u8>>;
+
+    fn dimensions(&self) -> (u32, u32) {
+        let desc = self.decoder.desc();
+        (desc.width as u32, desc.height as u32)
+    }
+
+    fn color_type(&self) -> ColorType {
+        match self.decoder.desc().channels {
+            ChanelMode::Rgb => ColorType::Rgb8,
+            ChanelMode::Rgba => ColorType::Rgba8,
+        }
+    }
+
+    fn into_reader(mut self) -> ImageResult<Self::Reader> {
+        // buffer grows with decoded pixels instead of trusting size in header
+        let (pixels, _) = decode::decode_all(&mut self.decoder).map_err(decoding_error)?;
+        Ok(Cursor::new(pixels))
+    }
+
+    fn read_image(mut self, buf: &mut [u8]) -> ImageResult<()> {
+        assert_eq!(buf.len() as u64, self.total_bytes());
+        self.decoder.read_rows(buf).map_err(decoding_error)?;
+        Ok(())
+    }
+
+    fn set_limits(&mut self, limits: Limits) -> ImageResult<()> {
+        limits.check_support(&LimitSupport::default())?;
+        let (width, height) = self.dimensions();
+        limits.check_dimensions(width, height)?;
+        if limits.max_alloc.is_some_and(|max| self.total_bytes() > max) {
+            return Err(ImageError::Limits(LimitError::from_kind(
+                LimitErrorKind::InsufficientMemory,
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// QOI encoder implementing `image::ImageEncoder`.
+///
+/// Accepts RGB8, RGBA8 and gray L8, LA8 pixels, which are written as RGB and RGBA.
+pub struct QoiImageEncoder<W: Write> {
+    writer: W,
+    colorspace: Colorspace,
+}
+
+impl<W: Write> QoiImageEncoder<W> {
+    /// Encoder writing images tagged as sRGB to `writer`.
+    pub fn new(writer: W) -> Self {
+        Self::with_colorspace(writer, Colorspace::Srgb)
+    }
+
+    /// Encoder writing images tagged with `colorspace` to `writer`.
+    pub fn with_colorspace(writer: W, colorspace: Colorspace) -> Self {
+        Self { writer, colorspace }
+    }
+}
+
+impl<W: Write> ImageEncoder for QoiImageEncoder<W> {
+    fn write_image(
+        mut self,
+        buf: &[u8],
+        width: u32,
+        height: u32,
+        color_type: ColorType,
+    ) -> ImageResult<()> {
+        let layout = match color_type {
+            ColorType::Rgb8 => PixelLayout::Rgb,
+            ColorType::Rgba8 => PixelLayout::Rgba,
+            ColorType::L8 => PixelLayout::L,
+            ColorType::La8 => PixelLayout::La,
+            _ => {
+                return Err(ImageError::Unsupported(
+                    UnsupportedError::from_format_and_kind(
+                        format_hint(),
+                        UnsupportedErrorKind::Color(color_type.into()),
+                    ),
+                ))
+            }
+        };
+        let desc = QoiDescriptor {
+            width: width as usize,
+            height: height as usize,
+            channels: layout.channels(),
+            colorspace: self.colorspace,
+        };
+        let bytes = qoi_encode_with_layout(buf, &desc, layout).map_err(encoding_error)?;
+        self.writer.write_all(&bytes)?;
+        Ok(())
+    }
+}
+
+/// Pixels of `image` in layout closest to its color type, with descriptor of QOI image.
+///
+/// RGB and gray images stay without alpha, other types are converted to 8 bits per channel.
+/// Useful to encode `image` with options or row by row with [`QoiEncoder`](struct.QoiEncoder.html).
+pub fn qoi_dynamic_pixels(image: &DynamicImage) -> (Cow<'_, [u8]>, QoiDescriptor, PixelLayout) {
+    let (pixels, layout) = match image {
+        DynamicImage::ImageRgb8(image) => {
+            (Cow::Borrowed(image.as_raw().as_slice()), PixelLayout::Rgb)
+        }
+        DynamicImage::ImageRgba8(image) => {
+            (Cow::Borrowed(image.as_raw().as_slice()), PixelLayout::Rgba)
+        }
+        DynamicImage::ImageLuma8(image) => {
+            (Cow::Borrowed(image.as_raw().as_slice()), PixelLayout::L)
+        }
+        DynamicImage::ImageLumaA8(image) => {
+            (Cow::Borrowed(image.as_raw().as_slice()), PixelLayout::La)
+        }
+        DynamicImage::ImageLuma16(_) => (Cow::Owned(image.to_luma8().into_raw()), PixelLayout::L),
+        DynamicImage::ImageLumaA16(_) => (
+            Cow::Owned(image.to_luma_alpha8().into_raw()),
+            PixelLayout::La,
+        ),
+        _ if image.color().has_alpha() => {
+            (Cow::Owned(image.to_rgba8().into_raw()), PixelLayout::Rgba)
+        }
+        _ => (Cow::Owned(image.to_rgb8().into_raw()), PixelLayout::Rgb),
+    };
+    let desc = QoiDescriptor {
+        width: image.width() as usize,
+        height: image.height() as usize,
+        channels: layout.channels(),
+        colorspace: Colorspace::Srgb,
+    };
+    (pixels, desc, layout)
+}
+
+/// Same as [`qoi_dynamic_pixels`](fn.qoi_dynamic_pixels.html) but takes `image`,
+/// so buffer of 8-bit RGB(A) and gray images is returned without copying.
+pub fn qoi_dynamic_into_pixels(image: DynamicImage) -> (Vec<u8>, QoiDescriptor, PixelLayout) {
+    let desc = QoiDescriptor {
+        width: image.width() as usize,
+        height: image.height() as usize,
+        channels: ChanelMode::Rgb,
+        colorspace: Colorspace::Srgb,
+    };
+    let (pixels, layout) = match image {
+        DynamicImage::ImageRgb8(image) => (image.into_raw(), PixelLayout::Rgb),
+        DynamicImage::ImageRgba8(image) => (image.into_raw(), PixelLayout::Rgba),
+        DynamicImage::ImageLuma8(image) => (image.into_raw(), PixelLayout::L),
+        DynamicImage::ImageLumaA8(image) => (image.into_raw(), PixelLayout::La),
+        image => {
+            let (pixels, _, layout) = qoi_dynamic_pixels(&image);
+            (pixels.into_owned(), layout)
+        }
+    };
+    let desc = QoiDescriptor {
+        channels: layout.channels(),
+        ..desc
+    };
+    (pixels, desc, layout)
+}
+
+/// Encode `image` keeping RGB images without alpha channel.
+pub fn qoi_encode_dynamic(image: &DynamicImage) -> Result<Vec<u8>, QoiError> {
+    let (pixels, desc, layout) = qoi_dynamic_pixels(image);
+    qoi_encode_with_layout(&pixels, &desc, layout)
+}
+
+/// Same as [`qoi_encode_dynamic`](fn.qoi_encode_dynamic.html) but encodes bands of image
+/// on rayon threads like [`qoi_encode_parallel`](fn.qoi_encode_parallel.html).
+#[cfg(feature = "parallel")]
+pub fn qoi_encode_dynamic_parallel(image: &DynamicImage) -> Result<Vec<u8>, QoiError> {
+    let (pixels, desc, layout) = qoi_dynamic_pixels(image);
+    qoi_encode_parallel_with_layout(&pixels, &desc, layout)
+}
+
+/// Decode QOI image in `data` into `DynamicImage::ImageRgb8` or `ImageRgba8` by its channels.
+pub fn qoi_decode_dynamic(data: &[u8]) -> Result<DynamicImage, QoiError> {
+    qoi_decode_dynamic_with_limits(data, DecodeLimits::default())
+}
+
+/// Same as [`qoi_decode_dynamic`](fn.qoi_decode_dynamic.html) but header is checked
+/// against `limits`.
+pub fn qoi_decode_dynamic_with_limits(
+    data: &[u8],
+    limits: DecodeLimits,
+) -> Result<DynamicImage, QoiError> {
+    let options = DecodeOptions {
+        limits,
+        ..DecodeOptions::default()
+    };
+    let (pixels, desc) = qoi_decode_from_slice_with_options(data, &options)?;
+    let (width, height) = (desc.width as u32, desc.height as u32);
+    let image = match desc.channels {
+        ChanelMode::Rgb => RgbImage::from_raw(width, height, pixels).map(DynamicImage::ImageRgb8),
+        ChanelMode::Rgba => {
+            RgbaImage::from_raw(width, height, pixels).map(DynamicImage::ImageRgba8)
+        }
+    };
+    Ok(image.expect("decoded buffer matches dimensions"))
+}
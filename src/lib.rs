@@ -0,0 +1,1350 @@
+//! # QOI encoder and decoder
+//!
+//! This crate contains implementations of a [`qoi_encode`](fn.qoi_encode.html)
+//! and [`qoi_decode`](fn.qoi_decode.html) functions
+//! similar to [`qoi.h`](https://github.com/phoboslab/qoi) by Dominic Szablewski.
+//!
+//! ## Decode Image
+//!
+//! [`qoi_decode`](fn.qoi_decode.html) takes `impl Read` which must provide bytes
+//! of qoi file and optionally [`ChanelMode`](enum.ChanelMode.html).
+//! It will return `Vec<u8>` containing flat pixels in RGBA or RGB order and
+//! [`QoiDescriptor`](struct.QoiDescriptor.html) with description of an image,
+//! or [`QoiError`](enum.QoiError.html).
+//! You should use `BufReader` to achieve better performance.
+//! If only dimensions are needed use [`qoi_read_header`](fn.qoi_read_header.html).
+//! If whole file is already in memory use
+//! [`qoi_decode_from_slice`](fn.qoi_decode_from_slice.html) which is much faster.
+//!
+//! ### Example of decoding pixels from `.qoi` file:
+
+//! ```
+//! use std::fs::File;
+//! use std::io::BufReader;
+//! use qoi::*;
+//!
+//! // load file and get bytes (use `BufReader` to speed up reads)
+//! let file = File::open("qoi_test_images/wikipedia_008.qoi").unwrap();
+//! let mut bytes = BufReader::new(file);
+//! // get pixels and descriptor
+//! let (data, desc) = qoi_decode(bytes, None).unwrap();
+//! ```
+//!
+//! ## Encode Image
+//! [`qoi_encode`](fn.qoi_encode.html) function takes `&[u8]` of flat pixel value
+//! RGB or RGBA, and [`QoiDescriptor`](struct.QoiDescriptor.html).
+//! Qoi format has hard limit on pixel count so your image must contain less than
+//! `QOI_PIXELS_MAX` pixels otherwise this function will return an error.
+//! Pixels in other order, like BGRA, or gray pixels can be encoded with
+//! [`qoi_encode_with_layout`](fn.qoi_encode_with_layout.html) and decoded with
+//! `*_with_layout` variants of decoding functions.
+//!
+//! ### Example of encoding pixels into `.qoi` file:
+//! ```
+//! use std::fs::File;
+//! use std::io::Write;
+//! use qoi::*;
+//!
+//! // get pixels and make valid descriptor
+//! // pixels must be laid out in order RGB(A)
+//! let pixels = [255, 0, 0, 15, 1, 255, 255, 255, 191, 255, 0, 0, 15, 1, 74];
+//! let desc = QoiDescriptor {
+//!     width: pixels.len() / 3,
+//!     height: 1,
+//!     channels: ChanelMode::Rgb,
+//!     colorspace: Colorspace::Linear,
+//! };
+//! let bytes = qoi_encode(&pixels, &desc).unwrap();
+//! let mut f = File::create(std::env::temp_dir().join("example.qoi")).unwrap();
+//! f.write_all(bytes.as_slice()).unwrap();
+//! ```
+//!
+//! ## Features
+//!
+//! * `std` (default) enables `impl Read`/`impl Write` based api and implies `alloc`.
+//! * `alloc` enables functions returning `Vec<u8>`.
+//! * `image` implements `image::ImageDecoder` and `image::ImageEncoder` with
+//!   [`QoiImageDecoder`](struct.QoiImageDecoder.html) and
+//!   [`QoiImageEncoder`](struct.QoiImageEncoder.html), implies `std`.
+//! * `parallel` enables [`qoi_encode_parallel`](fn.qoi_encode_parallel.html) encoding
+//!   bands of image on rayon threads, implies `std`.
+//! * `cli` (default) enables dependencies of `qoiconv-rs` command line tool.
+//!
+//! Without `std` and `alloc` crate is `#![no_std]`, use [`qoi_encode_into`](fn.qoi_encode_into.html)
+//! and [`qoi_decode_from_slice_into`](fn.qoi_decode_from_slice_into.html) with your own buffers.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+use core::fmt;
+
+mod colorspace;
+mod decode;
+mod encode;
+#[cfg(feature = "image")]
+mod image_codec;
+mod simd;
+mod stats;
+pub use colorspace::*;
+pub use decode::*;
+pub use encode::*;
+#[cfg(feature = "image")]
+pub use image_codec::*;
+pub use stats::*;
+
+///  Describes the input pixel data.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct QoiDescriptor {
+    pub width: usize,
+    pub height: usize,
+    pub channels: ChanelMode,
+    pub colorspace: Colorspace,
+}
+
+/// Rgba of Rgb mode.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ChanelMode {
+    Rgb = 3,
+    Rgba = 4,
+}
+/// Order of channels in flat pixel buffer passed to or returned by the codec.
+///
+/// QOI stream itself is always RGB(A), layout only changes how pixels are
+/// read on encoding and written on decoding, so no extra swizzled copy is needed.
+/// Gray `L` and `La` pixels are expanded to RGB(A) on encoding, `gray` of
+/// [`DecodeOptions`](struct.DecodeOptions.html) decides what happens to colored pixels
+/// decoded into them.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PixelLayout {
+    Rgb,
+    Rgba,
+    Bgr,
+    Bgra,
+    Argb,
+    Abgr,
+    L,
+    La,
+}
+
+/// How pixels are decoded into `PixelLayout::L` or `PixelLayout::La`.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum GrayMode {
+    /// Fail with `QoiError::NotGray` on first pixel which isn't gray.
+    #[default]
+    Strict,
+    /// Convert colored pixels to their luma, gray pixels are kept as is.
+    Lossy,
+}
+
+impl PixelLayout {
+    /// Number of channels of QOI image with pixels in this layout.
+    pub const fn channels(self) -> ChanelMode {
+        match self {
+            Self::Rgb | Self::Bgr | Self::L => ChanelMode::Rgb,
+            Self::Rgba | Self::Bgra | Self::Argb | Self::Abgr | Self::La => ChanelMode::Rgba,
+        }
+    }
+
+    /// Number of bytes of pixel in this layout.
+    pub const fn bytes_per_pixel(self) -> usize {
+        match self {
+            Self::L => 1,
+            Self::La => 2,
+            _ => self.channels() as usize,
+        }
+    }
+
+    /// Position of alpha byte in pixel stored in this layout.
+    const fn alpha_byte(self) -> Option<usize> {
+        match self {
+            Self::Rgba | Self::Bgra => Some(3),
+            Self::Argb | Self::Abgr => Some(0),
+            Self::La => Some(1),
+            Self::Rgb | Self::Bgr | Self::L => None,
+        }
+    }
+
+    /// Read pixel stored in this layout, `pixel` must have `bytes_per_pixel` bytes.
+    #[inline(always)]
+    fn read(self, pixel: &[u8]) -> QoiRGBA {
+        match (self, pixel) {
+            (Self::Rgb, &[r, g, b]) => QoiRGBA::new(r, g, b, 255),
+            (Self::Rgba, &[r, g, b, a]) => QoiRGBA::new(r, g, b, a),
+            (Self::Bgr, &[b, g, r]) => QoiRGBA::new(r, g, b, 255),
+            (Self::Bgra, &[b, g, r, a]) => QoiRGBA::new(r, g, b, a),
+            (Self::Argb, &[a, r, g, b]) => QoiRGBA::new(r, g, b, a),
+            (Self::Abgr, &[a, b, g, r]) => QoiRGBA::new(r, g, b, a),
+            (Self::L, &[l]) => QoiRGBA::new(l, l, l, 255),
+            (Self::La, &[l, a]) => QoiRGBA::new(l, l, l, a),
+            _ => unreachable!("pixel must have as many bytes as layout"),
+        }
+    }
+
+    /// Bytes of `pixel` in this layout, only first `bytes_per_pixel` bytes are meaningful.
+    /// `None` if layout is gray, `gray` is strict and `pixel` isn't gray.
+    #[inline(always)]
+    fn write(self, pixel: QoiRGBA, gray: GrayMode) -> Option<[u8; 4]> {
+        let QoiRGBA { r, g, b, a } = pixel;
+        let gray = || match gray {
+            _ if r == g && g == b => Some(r),
+            GrayMode::Strict => None,
+            // BT.601 weights scaled to 256, exact for gray pixels
+            GrayMode::Lossy => {
+                Some(((r as u32 * 77 + g as u32 * 150 + b as u32 * 29 + 128) >> 8) as u8)
+            }
+        };
+        Some(match self {
+            Self::Rgb | Self::Rgba => [r, g, b, a],
+            Self::Bgr | Self::Bgra => [b, g, r, a],
+            Self::Argb => [a, r, g, b],
+            Self::Abgr => [a, b, g, r],
+            Self::L => [gray()?, 0, 0, 0],
+            Self::La => [gray()?, a, 0, 0],
+        })
+    }
+}
+
+impl From<ChanelMode> for PixelLayout {
+    fn from(channels: ChanelMode) -> Self {
+        match channels {
+            ChanelMode::Rgb => Self::Rgb,
+            ChanelMode::Rgba => Self::Rgba,
+        }
+    }
+}
+
+/// Colorspace of image, stored in header as a tag.
+///
+/// Codec doesn't change pixels by it, they can be converted between colorspaces with
+/// [`qoi_convert_colorspace`](fn.qoi_convert_colorspace.html) or `colorspace`
+/// of [`DecodeOptions`](struct.DecodeOptions.html).
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Colorspace {
+    Srgb = 0,
+    Linear = 1,
+}
+
+/// Error returned by [`qoi_encode`](fn.qoi_encode.html) and [`qoi_decode`](fn.qoi_decode.html).
+#[derive(Debug)]
+pub enum QoiError {
+    /// File doesn't start with `qoif`.
+    BadMagic([u8; 4]),
+    /// Number of channels in header is neither 3 nor 4.
+    InvalidChannels(u8),
+    /// Colorspace in header is neither 0 nor 1.
+    InvalidColorspace(u8),
+    /// Width or height is zero.
+    ZeroDimensions,
+    /// Image has more pixels than allowed, fewer than `QOI_PIXELS_MAX` unless raised in options,
+    /// or its width or height doesn't fit into header.
+    TooManyPixels,
+    /// Stream ended before all pixels were decoded.
+    Truncated,
+    /// Stream doesn't end with `QOI_PADDING`.
+    BadPadding,
+    /// There are bytes after `QOI_PADDING`.
+    TrailingData,
+    /// Last run goes past the last pixel of image.
+    RunOverflow,
+    /// Length of pixel buffer doesn't match descriptor.
+    BufferSizeMismatch { expected: usize, actual: usize },
+    /// Image is bigger than [`DecodeLimits`](struct.DecodeLimits.html) allow.
+    LimitsExceeded,
+    /// Row stride is shorter than row of pixels.
+    InvalidStride { row_len: usize, stride: usize },
+    /// Decoded pixel isn't gray and `gray` of `DecodeOptions` is `GrayMode::Strict`.
+    NotGray,
+    /// Pixel layout has different number of channels than descriptor.
+    LayoutMismatch {
+        channels: ChanelMode,
+        layout: PixelLayout,
+    },
+    /// Underlying reader or writer failed.
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
+}
+
+impl fmt::Display for QoiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadMagic(magic) => write!(f, "unexpected header: {magic:?}"),
+            Self::InvalidChannels(channels) => {
+                write!(f, "unexpected number of color channels: {channels}")
+            }
+            Self::InvalidColorspace(colorspace) => {
+                write!(f, "unexpected colorspace: {colorspace}")
+            }
+            Self::ZeroDimensions => write!(f, "width or height is zero"),
+            Self::TooManyPixels => write!(f, "exceeded maximum safe pixel count"),
+            Self::Truncated => write!(f, "unexpected end of qoi stream"),
+            Self::BadPadding => write!(f, "missing or invalid end padding"),
+            Self::TrailingData => write!(f, "unexpected data after end padding"),
+            Self::RunOverflow => write!(f, "run goes past the last pixel"),
+            Self::BufferSizeMismatch { expected, actual } => {
+                write!(f, "expected buffer of {expected} bytes, got {actual}")
+            }
+            Self::LimitsExceeded => write!(f, "image exceeds decode limits"),
+            Self::InvalidStride { row_len, stride } => {
+                write!(f, "stride {stride} is shorter than row of {row_len} bytes")
+            }
+            Self::NotGray => write!(f, "image has colored pixels"),
+            Self::LayoutMismatch { channels, layout } => {
+                write!(
+                    f,
+                    "pixel layout {layout:?} doesn't match {channels:?} channels"
+                )
+            }
+            #[cfg(feature = "std")]
+            Self::Io(err) => write!(f, "io error: {err}"),
+        }
+    }
+}
+
+impl core::error::Error for QoiError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            #[cfg(feature = "std")]
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for QoiError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+struct QoiRGBA {
+    r: u8,
+    g: u8,
+    b: u8,
+    a: u8,
+}
+impl QoiRGBA {
+    /// Create new RGBA pixel form individual values.
+    fn new(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self { r, g, b, a }
+    }
+}
+
+/// Encodes index in pixel buffer 00xxxxxx
+const QOI_OP_INDEX: u8 = 0x00;
+/// Encodes delta of pixels 01xxxxxx
+const QOI_OP_DIFF: u8 = 0x40;
+/// Encodes luma encoding of pixels 10xxxxxx
+const QOI_OP_LUMA: u8 = 0x80;
+/// Encodes run encoding of pixels 11xxxxxx
+const QOI_OP_RUN: u8 = 0xc0;
+/// Encodes RGB pixel op 11111110
+const QOI_OP_RGB: u8 = 0xfe;
+/// Encodes RGBA pixel op 11111111
+const QOI_OP_RGBA: u8 = 0xff;
+/// Select only first two bits 11000000
+const QOI_MASK: u8 = 0xc0;
+
+/// Number of bytes in op starting with `op_byte`.
+const fn op_len(op_byte: u8) -> usize {
+    match op_byte {
+        QOI_OP_RGB => 4,
+        QOI_OP_RGBA => 5,
+        _ if op_byte & QOI_MASK == QOI_OP_LUMA => 2,
+        _ => 1,
+    }
+}
+
+/// Hash of Rgba pixel.
+const fn color_hash(pixel: QoiRGBA) -> usize {
+    let QoiRGBA { r, g, b, a } = pixel;
+    r as usize * 3 + g as usize * 5 + b as usize * 7 + a as usize * 11
+}
+/// Size of header.
+const QOI_HEADER_SIZE: usize = 14;
+
+/// Maximum safe pixel count.
+///
+/// 2GB is the max file size that this implementation can safely handle. We guard
+/// against anything larger than that, assuming the worst case with 5 bytes per
+/// pixel, rounded down to a nice clean value. 400 million pixels ought to be
+/// enough for anybody.
+///
+/// Like `qoi.h` images with this many pixels or more are rejected by default,
+/// [`EncodeOptions`](struct.EncodeOptions.html) and
+/// [`DecodeLimits`](struct.DecodeLimits.html) can raise the limit on 64-bit targets.
+pub const QOI_PIXELS_MAX: usize = 400_000_000;
+/// Default `max_pixels` of options, images must have fewer than `QOI_PIXELS_MAX` pixels.
+const DEFAULT_MAX_PIXELS: usize = QOI_PIXELS_MAX - 1;
+/// Size of qoi's padding.
+const QOI_PADDING_SIZE: usize = 8;
+/// Padding for qoi file.
+const QOI_PADDING: [u8; QOI_PADDING_SIZE] = [0, 0, 0, 0, 0, 0, 0, 1];
+
+/// Number of pixels in image, checked against `max_pixels`.
+///
+/// On targets other than 64-bit `max_pixels` can't go past the default one,
+/// as bigger images wouldn't fit into address space.
+fn pixel_count(desc: &QoiDescriptor, max_pixels: usize) -> Result<usize, QoiError> {
+    if desc.width == 0 || desc.height == 0 {
+        return Err(QoiError::ZeroDimensions);
+    }
+
+    let max_pixels = match cfg!(target_pointer_width = "64") {
+        true => max_pixels,
+        false => max_pixels.min(DEFAULT_MAX_PIXELS),
+    };
+    if u32::try_from(desc.width).is_err() || u32::try_from(desc.height).is_err() {
+        return Err(QoiError::TooManyPixels);
+    }
+
+    desc.width
+        .checked_mul(desc.height)
+        .filter(|&pixels| pixels <= max_pixels)
+        .ok_or(QoiError::TooManyPixels)
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    #[test]
+    fn inverse_application_test() {
+        let pixels = [255, 0, 0, 15, 1, 255, 255, 255, 191, 255, 0, 0, 15, 1, 74];
+        // [255, 255, 255, 107, 255, 255, 255, 255, 255];
+        // [0, 38, 0, 0, 0, 0, 0, 38, 0]
+        let desc = QoiDescriptor {
+            width: pixels.len() / 3,
+            height: 1,
+            channels: ChanelMode::Rgb,
+            colorspace: Colorspace::Linear,
+        };
+        let bytes = qoi_encode(&pixels, &desc).unwrap();
+        dbg!(&bytes);
+        let (pixels_, _desc) = qoi_decode(Cursor::new(bytes), None).unwrap();
+        dbg!(&pixels_);
+        assert_eq!(pixels_, pixels);
+    }
+    #[test]
+    fn indexing_simple() {
+        let pixels = [0, 0, 1, 0, 0, 0, 0, 0, 1];
+        // [255, 255, 255, 107, 255, 255, 255, 255, 255];
+        // [0, 38, 0, 0, 0, 0, 0, 38, 0]
+        let desc = QoiDescriptor {
+            width: pixels.len() / 3,
+            height: 1,
+            channels: ChanelMode::Rgb,
+            colorspace: Colorspace::Linear,
+        };
+        let bytes = qoi_encode(&pixels, &desc).unwrap();
+        dbg!(&bytes);
+        let (pixels_, _desc) = qoi_decode(Cursor::new(bytes), None).unwrap();
+        dbg!(&pixels_);
+        assert_eq!(pixels_, pixels);
+    }
+    #[test]
+    fn first_pixel_zero() {
+        let pixels = [0, 0, 0, 0, 0, 1];
+        let desc = QoiDescriptor {
+            width: pixels.len() / 3,
+            height: 1,
+            channels: ChanelMode::Rgb,
+            colorspace: Colorspace::Linear,
+        };
+        let bytes = qoi_encode(&pixels, &desc).unwrap();
+        dbg!(&bytes);
+        let (pixels_decoded, _desc) = qoi_decode(Cursor::new(bytes), None).unwrap();
+        dbg!(&pixels_decoded);
+        assert_eq!(pixels_decoded, pixels);
+    }
+    #[test]
+    fn decode_errors() {
+        let err = qoi_decode(Cursor::new(b"qoix\0\0\0\x01\0\0\0\x01\x03\0"), None).unwrap_err();
+        assert!(matches!(err, QoiError::BadMagic(magic) if &magic == b"qoix"));
+
+        let err = qoi_decode(Cursor::new(b"qoif\0\0\0\x01\0\0\0\x01\x05\0"), None).unwrap_err();
+        assert!(matches!(err, QoiError::InvalidChannels(5)));
+
+        let err = qoi_decode(Cursor::new(b"qoif\0\0\0\x01\0\0\0\x01\x03\x02"), None).unwrap_err();
+        assert!(matches!(err, QoiError::InvalidColorspace(2)));
+
+        let err = qoi_decode(Cursor::new(b"qoif\0\0\0\0\0\0\0\x01\x03\0"), None).unwrap_err();
+        assert!(matches!(err, QoiError::ZeroDimensions));
+
+        let err = qoi_decode(Cursor::new(b"qoif\0\0\0\x02\0\0\0\x01\x03\0\xfe"), None).unwrap_err();
+        assert!(matches!(err, QoiError::Truncated));
+    }
+    #[test]
+    fn buffer_size_mismatch() {
+        let desc = QoiDescriptor {
+            width: 2,
+            height: 2,
+            channels: ChanelMode::Rgb,
+            colorspace: Colorspace::Srgb,
+        };
+        let err = qoi_encode(&[0; 11], &desc).unwrap_err();
+        assert!(matches!(
+            err,
+            QoiError::BufferSizeMismatch {
+                expected: 12,
+                actual: 11
+            }
+        ));
+    }
+    #[test]
+    fn overflowing_descriptor() {
+        #[cfg(target_pointer_width = "64")]
+        let sizes = [(usize::MAX, 2), (1 << 32, 1 << 32), (1 << 62, 4)];
+        #[cfg(target_pointer_width = "32")]
+        let sizes = [(usize::MAX, 2), (1 << 16, 1 << 16), (1 << 30, 4)];
+        for (width, height) in sizes {
+            let desc = QoiDescriptor {
+                width,
+                height,
+                channels: ChanelMode::Rgba,
+                colorspace: Colorspace::Srgb,
+            };
+            let err = qoi_encode(&[0; 16], &desc).unwrap_err();
+            assert!(matches!(err, QoiError::TooManyPixels));
+        }
+
+        let header = b"qoif\xff\xff\xff\xff\xff\xff\xff\xff\x04\0";
+        let err = qoi_decode(Cursor::new(header), None).unwrap_err();
+        assert!(matches!(err, QoiError::TooManyPixels));
+    }
+    #[test]
+    fn streaming_encoder() {
+        let pixels: Vec<u8> = (0..12 * 7 * 4).map(|i| (i * i / 7 % 251) as u8).collect();
+        let desc = QoiDescriptor {
+            width: 12,
+            height: 7,
+            channels: ChanelMode::Rgba,
+            colorspace: Colorspace::Srgb,
+        };
+        let bytes = qoi_encode(&pixels, &desc).unwrap();
+
+        let mut encoder = QoiEncoder::new(Vec::new(), &desc).unwrap();
+        for row in pixels.chunks(12 * 4) {
+            encoder.write_row(row).unwrap();
+        }
+        assert_eq!(encoder.finish().unwrap(), bytes);
+
+        let mut encoder = QoiEncoder::new(Vec::new(), &desc).unwrap();
+        for chunk in pixels.chunks(5) {
+            encoder.write_pixels(chunk).unwrap();
+        }
+        assert_eq!(encoder.finish().unwrap(), bytes);
+
+        let mut encoder = QoiEncoder::new(Vec::new(), &desc).unwrap();
+        encoder.write_pixels(&pixels[..10]).unwrap();
+        assert!(matches!(
+            encoder.finish().unwrap_err(),
+            QoiError::BufferSizeMismatch { actual: 10, .. }
+        ));
+
+        // end of file from writer isn't truncated stream
+        struct EofWriter;
+        impl std::io::Write for EofWriter {
+            fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+                Err(std::io::ErrorKind::UnexpectedEof.into())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        let err = QoiEncoder::new(EofWriter, &desc).err().unwrap();
+        assert!(matches!(err, QoiError::Io(_)));
+    }
+    #[test]
+    fn incremental_decoder() {
+        let pixels: Vec<u8> = (0..9 * 5 * 3).map(|i| (i * i / 5 % 253) as u8).collect();
+        let desc = QoiDescriptor {
+            width: 9,
+            height: 5,
+            channels: ChanelMode::Rgb,
+            colorspace: Colorspace::Srgb,
+        };
+        let bytes = qoi_encode(&pixels, &desc).unwrap();
+
+        let mut decoder = QoiDecoder::new(Cursor::new(&bytes), None).unwrap();
+        assert_eq!(decoder.desc(), &desc);
+        let rows: Vec<u8> = decoder.scanlines().flat_map(Result::unwrap).collect();
+        assert_eq!(rows, pixels);
+
+        let mut decoder = QoiDecoder::new(Cursor::new(&bytes), None).unwrap();
+        let mut buf = [0; 9 * 3 * 2];
+        let mut decoded = Vec::new();
+        while decoder.rows_left() > 0 {
+            let rows = decoder.read_rows(&mut buf).unwrap();
+            decoded.extend_from_slice(&buf[..rows * 9 * 3]);
+        }
+        assert_eq!(decoded, pixels);
+        assert_eq!(decoder.read_rows(&mut buf).unwrap(), 0);
+    }
+    #[test]
+    fn read_header() {
+        let desc = qoi_read_header(&include_bytes!("../qoi_test_images/dice.qoi")[..]).unwrap();
+        assert_eq!(
+            desc,
+            QoiDescriptor {
+                width: 800,
+                height: 600,
+                channels: ChanelMode::Rgba,
+                colorspace: Colorspace::Srgb,
+            }
+        );
+        let err = qoi_read_header(&b"qoif\0\0\0\x01"[..]).unwrap_err();
+        assert!(matches!(err, QoiError::Truncated));
+
+        // header of image over default cap is accepted only with raised limits
+        let mut big = b"qoif".to_vec();
+        big.extend(20000u32.to_be_bytes());
+        big.extend(20000u32.to_be_bytes());
+        big.extend([4, 0]);
+        let err = qoi_read_header_from_slice(&big).unwrap_err();
+        assert!(matches!(err, QoiError::TooManyPixels));
+        let raised = DecodeLimits {
+            max_pixels: usize::MAX,
+            ..DecodeLimits::default()
+        };
+        #[cfg(target_pointer_width = "64")]
+        {
+            let desc = qoi_read_header_from_slice_with_limits(&big, raised).unwrap();
+            assert_eq!((desc.width, desc.height), (20000, 20000));
+            assert_eq!(qoi_read_header_with_limits(&big[..], raised).unwrap(), desc);
+        }
+        let lowered = DecodeLimits {
+            max_bytes: 800 * 600 * 4 - 1,
+            ..raised
+        };
+        let dice = &include_bytes!("../qoi_test_images/dice.qoi")[..];
+        let err = qoi_read_header_with_limits(dice, lowered).unwrap_err();
+        assert!(matches!(err, QoiError::LimitsExceeded));
+        let err = qoi_read_header_from_slice_with_limits(dice, lowered).unwrap_err();
+        assert!(matches!(err, QoiError::LimitsExceeded));
+    }
+    #[test]
+    fn decode_into_buffer() {
+        let pixels: Vec<u8> = (0..6 * 4 * 4).map(|i| (i * 31 % 256) as u8).collect();
+        let desc = QoiDescriptor {
+            width: 6,
+            height: 4,
+            channels: ChanelMode::Rgba,
+            colorspace: Colorspace::Srgb,
+        };
+        let bytes = qoi_encode(&pixels, &desc).unwrap();
+
+        let mut buf = vec![0; pixels.len()];
+        assert_eq!(
+            qoi_decode_into(&bytes[..], &mut buf, None, None).unwrap(),
+            desc
+        );
+        assert_eq!(buf, pixels);
+
+        let err = qoi_decode_into(&bytes[..], &mut buf[1..], None, None).unwrap_err();
+        assert!(matches!(err, QoiError::BufferSizeMismatch { .. }));
+
+        let err = qoi_decode_into(&bytes[..], &mut buf, None, Some(8)).unwrap_err();
+        assert!(matches!(err, QoiError::InvalidStride { row_len: 24, .. }));
+
+        let mut padded = vec![7; 32 * 4];
+        qoi_decode_into(&bytes[..], &mut padded, None, Some(32)).unwrap();
+        for (row, padded) in pixels.chunks(24).zip(padded.chunks(32)) {
+            assert_eq!(&padded[..24], row);
+            assert!(padded[24..].iter().all(|&byte| byte == 7));
+        }
+    }
+    #[test]
+    fn decode_from_slice() {
+        let images: [&[u8]; 3] = [
+            include_bytes!("../qoi_test_images/dice.qoi"),
+            include_bytes!("../qoi_test_images/testcard.qoi"),
+            include_bytes!("../qoi_test_images/qoi_logo.qoi"),
+        ];
+        for bytes in images {
+            for channels in [None, Some(ChanelMode::Rgb), Some(ChanelMode::Rgba)] {
+                let expected = qoi_decode(bytes, channels).unwrap();
+                assert_eq!(qoi_decode_from_slice(bytes, channels).unwrap(), expected);
+            }
+            let truncated = &bytes[..bytes.len() / 2];
+            let err = qoi_decode_from_slice(truncated, None).unwrap_err();
+            assert!(matches!(err, QoiError::Truncated));
+        }
+
+        // header of 10000x10000 image followed by 3 ops, rejected before allocating pixels
+        let mut bytes = b"qoif".to_vec();
+        bytes.extend(10000u32.to_be_bytes());
+        bytes.extend(10000u32.to_be_bytes());
+        bytes.extend([4, 0, 0xfd, 0xfd, 0xfd]);
+        let err = qoi_decode_from_slice(&bytes, None).unwrap_err();
+        assert!(matches!(err, QoiError::Truncated));
+    }
+    #[test]
+    fn slice_roundtrip_without_alloc() {
+        let pixels = [9, 9, 9, 9, 9, 9, 200, 10, 30, 201, 10, 29, 0, 0, 0, 7, 7, 7];
+        let desc = QoiDescriptor {
+            width: 3,
+            height: 2,
+            channels: ChanelMode::Rgb,
+            colorspace: Colorspace::Srgb,
+        };
+        let mut encoded = [0; 64];
+        let len = qoi_encode_into(&pixels, &desc, &mut encoded).unwrap();
+        assert_eq!(&encoded[..len], qoi_encode(&pixels, &desc).unwrap());
+        assert!(matches!(
+            qoi_encode_into(&pixels, &desc, &mut encoded[..20]),
+            Err(QoiError::BufferSizeMismatch { .. })
+        ));
+
+        let mut decoded = [0; 18];
+        let decoded_desc = qoi_decode_from_slice_into(&encoded[..len], &mut decoded, None).unwrap();
+        assert_eq!(decoded_desc, desc);
+        assert_eq!(decoded, pixels);
+    }
+    #[test]
+    fn pixel_layouts() {
+        let rgba: Vec<u8> = (0..7 * 3 * 4).map(|i| (i * i / 3 % 247) as u8).collect();
+        let layouts = [
+            (PixelLayout::Rgb, [0, 1, 2].as_slice()),
+            (PixelLayout::Rgba, &[0, 1, 2, 3]),
+            (PixelLayout::Bgr, &[2, 1, 0]),
+            (PixelLayout::Bgra, &[2, 1, 0, 3]),
+            (PixelLayout::Argb, &[3, 0, 1, 2]),
+            (PixelLayout::Abgr, &[3, 2, 1, 0]),
+        ];
+        for (layout, order) in layouts {
+            let desc = QoiDescriptor {
+                width: 7,
+                height: 3,
+                channels: layout.channels(),
+                colorspace: Colorspace::Srgb,
+            };
+            let pixels: Vec<u8> = rgba
+                .chunks(4)
+                .flat_map(|pixel| order.iter().map(|&i| pixel[i]))
+                .collect();
+            let expected: Vec<u8> = match desc.channels {
+                ChanelMode::Rgb => rgba
+                    .chunks(4)
+                    .flat_map(|pixel| &pixel[..3])
+                    .copied()
+                    .collect(),
+                ChanelMode::Rgba => rgba.clone(),
+            };
+            let bytes = qoi_encode(&expected, &desc).unwrap();
+
+            assert_eq!(
+                qoi_encode_with_layout(&pixels, &desc, layout).unwrap(),
+                bytes
+            );
+            let mut encoder = QoiEncoder::with_layout(Vec::new(), &desc, layout).unwrap();
+            for chunk in pixels.chunks(5) {
+                encoder.write_pixels(chunk).unwrap();
+            }
+            assert_eq!(encoder.finish().unwrap(), bytes);
+
+            let decoded = (pixels.clone(), desc.clone());
+            assert_eq!(qoi_decode_with_layout(&bytes[..], layout).unwrap(), decoded);
+            assert_eq!(
+                qoi_decode_from_slice_with_layout(&bytes, layout).unwrap(),
+                decoded
+            );
+        }
+
+        let desc = QoiDescriptor {
+            width: 7,
+            height: 3,
+            channels: ChanelMode::Rgb,
+            colorspace: Colorspace::Srgb,
+        };
+        let err = qoi_encode_with_layout(&rgba, &desc, PixelLayout::Bgra).unwrap_err();
+        assert!(matches!(
+            err,
+            QoiError::LayoutMismatch {
+                channels: ChanelMode::Rgb,
+                layout: PixelLayout::Bgra
+            }
+        ));
+    }
+    #[test]
+    fn gray_layouts() {
+        let la: Vec<u8> = (0..5 * 4 * 2).map(|i| (i * i / 9 % 241) as u8).collect();
+        let rgba: Vec<u8> = la
+            .chunks(2)
+            .flat_map(|p| [p[0], p[0], p[0], p[1]])
+            .collect();
+        let l: Vec<u8> = la.chunks(2).map(|p| p[0]).collect();
+        let rgb: Vec<u8> = l.iter().flat_map(|&l| [l, l, l]).collect();
+        for (gray, color, channels, layout) in [
+            (&la, &rgba, ChanelMode::Rgba, PixelLayout::La),
+            (&l, &rgb, ChanelMode::Rgb, PixelLayout::L),
+        ] {
+            let desc = QoiDescriptor {
+                width: 5,
+                height: 4,
+                channels,
+                colorspace: Colorspace::Srgb,
+            };
+            let bytes = qoi_encode(color, &desc).unwrap();
+            assert_eq!(qoi_encode_with_layout(gray, &desc, layout).unwrap(), bytes);
+            let mut encoder = QoiEncoder::with_layout(Vec::new(), &desc, layout).unwrap();
+            for row in gray.chunks(gray.len() / 4) {
+                encoder.write_row(row).unwrap();
+            }
+            assert_eq!(encoder.finish().unwrap(), bytes);
+
+            let decoded = (gray.clone(), desc.clone());
+            assert_eq!(qoi_decode_with_layout(&bytes[..], layout).unwrap(), decoded);
+            assert_eq!(
+                qoi_decode_from_slice_with_layout(&bytes, layout).unwrap(),
+                decoded
+            );
+        }
+
+        let bytes = include_bytes!("../qoi_test_images/dice.qoi");
+        let err = qoi_decode_from_slice_with_layout(bytes, PixelLayout::La).unwrap_err();
+        assert!(matches!(err, QoiError::NotGray));
+        let err = qoi_decode_with_layout(&bytes[..], PixelLayout::La).unwrap_err();
+        assert!(matches!(err, QoiError::NotGray));
+
+        let lossy = DecodeOptions {
+            layout: Some(PixelLayout::La),
+            gray: GrayMode::Lossy,
+            ..DecodeOptions::default()
+        };
+        let (pixels, desc) = qoi_decode_from_slice_with_options(bytes, &lossy).unwrap();
+        assert_eq!(pixels.len(), desc.width * desc.height * 2);
+        assert_eq!(
+            qoi_decode_with_options(&bytes[..], &lossy).unwrap().0,
+            pixels
+        );
+    }
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_bands() {
+        // transparent black, runs crossing bands and repeated colors for index ops
+        let pixels: Vec<u8> = (0..13 * 11)
+            .flat_map(|i: u32| match i % 17 {
+                0..=2 => [0, 0, 0, 0],
+                3..=9 => [10, 20, 30, 255],
+                _ => [(i * 7) as u8, (i / 3) as u8, 9, (i % 5 * 60) as u8],
+            })
+            .collect();
+        let desc = QoiDescriptor {
+            width: 13,
+            height: 11,
+            channels: ChanelMode::Rgba,
+            colorspace: Colorspace::Srgb,
+        };
+        let serial = qoi_encode(&pixels, &desc).unwrap();
+        assert_eq!(qoi_encode_parallel(&pixels, &desc).unwrap(), serial);
+
+        for band_rows in [1, 2, 5, 11] {
+            let bytes =
+                encode::encode_bands(&pixels, &desc, desc.channels.into(), band_rows).unwrap();
+            let (decoded, decoded_desc) = qoi_decode(&bytes[..], None).unwrap();
+            assert_eq!(decoded_desc, desc);
+            assert_eq!(decoded, pixels);
+            assert_eq!(qoi_decode_from_slice(&bytes, None).unwrap().0, pixels);
+        }
+    }
+    #[test]
+    fn bulk_runs_match_pixel_by_pixel() {
+        // runs shorter and longer than 62 pixels and than blocks scanned at once, last one ends image
+        let mut rgba = Vec::new();
+        for (i, len) in [1, 2, 3, 4, 5, 11, 12, 13, 61, 62, 63, 124, 125, 200, 1, 7]
+            .iter()
+            .enumerate()
+        {
+            let color = [(i * 40) as u8, 7, 200 - i as u8, 255 - i as u8 % 3];
+            for _ in 0..*len {
+                rgba.extend_from_slice(&color);
+            }
+        }
+        let width = rgba.len() / 4;
+        for layout in [
+            PixelLayout::Rgb,
+            PixelLayout::Rgba,
+            PixelLayout::Bgr,
+            PixelLayout::L,
+            PixelLayout::La,
+        ] {
+            let desc = QoiDescriptor {
+                width,
+                height: 1,
+                channels: layout.channels(),
+                colorspace: Colorspace::Srgb,
+            };
+            let pixels: Vec<u8> = rgba
+                .chunks(4)
+                .flat_map(|pixel| &pixel[..layout.bytes_per_pixel()])
+                .copied()
+                .collect();
+            // pixels split between chunks are encoded one by one
+            let mut encoder = QoiEncoder::with_layout(Vec::new(), &desc, layout).unwrap();
+            for byte in pixels.chunks(1) {
+                encoder.write_pixels(byte).unwrap();
+            }
+            let expected = encoder.finish().unwrap();
+            assert_eq!(
+                qoi_encode_with_layout(&pixels, &desc, layout).unwrap(),
+                expected
+            );
+        }
+
+        let rgb: Vec<u8> = (0..100 * 3).map(|i| (i * 13 % 256) as u8).collect();
+        let mut expanded = [[0; 4]; 100];
+        simd::rgb_to_rgba(&rgb, &mut expanded);
+        for (rgb, rgba) in rgb.chunks(3).zip(expanded) {
+            assert_eq!(rgba, [rgb[0], rgb[1], rgb[2], 255]);
+        }
+        let mut bytes = [5; 200];
+        bytes[121] = 6;
+        for size in 1..=4 {
+            assert_eq!(simd::run_length(&bytes, &[5; 4][..size]), 121 / size);
+            assert_eq!(simd::run_length(&bytes[122..], &[5; 4][..size]), 78 / size);
+        }
+    }
+    #[test]
+    fn op_stats() {
+        let pixels = [
+            9, 9, 9, 9, 9, 9, 9, 9, 9, 10, 9, 9, 9, 9, 9, 40, 50, 60, 50, 20, 10,
+        ];
+        let desc = QoiDescriptor {
+            width: 7,
+            height: 1,
+            channels: ChanelMode::Rgb,
+            colorspace: Colorspace::Srgb,
+        };
+        let (bytes, stats) = qoi_encode_with_stats(&pixels, &desc).unwrap();
+        assert_eq!(
+            stats,
+            QoiStats {
+                index: 1,
+                diff: 1,
+                luma: 1,
+                run: 1,
+                rgb: 2,
+                rgba: 0,
+                run_pixels: 2,
+                pixels: 7,
+                bytes: bytes.len(),
+            }
+        );
+        assert_eq!(stats.index_hit_rate(), 0.2);
+
+        for bytes in [
+            &include_bytes!("../qoi_test_images/dice.qoi")[..],
+            include_bytes!("../qoi_test_images/testcard_rgba.qoi"),
+        ] {
+            let stats = QoiStats::from_qoi(bytes).unwrap();
+            let ops = stats.index + stats.diff + stats.luma + stats.rgb + stats.rgba;
+            assert_eq!(ops + stats.run_pixels, stats.pixels);
+            assert_eq!(stats.bytes, bytes.len());
+            // missing padding isn't counted
+            let unpadded = &bytes[..bytes.len() - QOI_PADDING_SIZE];
+            assert_eq!(QoiStats::from_qoi(unpadded).unwrap().bytes, unpadded.len());
+            let err = QoiStats::from_qoi(&bytes[..bytes.len() / 2]).unwrap_err();
+            assert!(matches!(err, QoiError::Truncated));
+        }
+
+        // run of 3 pixels in image of 2 is counted only up to the last pixel
+        let mut overflow = b"qoif\0\0\0\x02\0\0\0\x01\x04\0\xc2".to_vec();
+        overflow.extend(QOI_PADDING);
+        let stats = QoiStats::from_qoi(&overflow).unwrap();
+        assert_eq!((stats.run, stats.run_pixels, stats.pixels), (1, 2, 2));
+        assert_eq!(stats.index_hit_rate(), 0.0);
+    }
+    #[test]
+    fn strict_validation() {
+        let valid = include_bytes!("../qoi_test_images/testcard.qoi").to_vec();
+        let len = valid.len();
+        let mut bad_padding = valid.clone();
+        bad_padding[len - 1] = 0;
+        let mut trailing = valid.clone();
+        trailing.push(0);
+        // 3 pixel run in 2 pixel image
+        let mut overflow = b"qoif\0\0\0\x02\0\0\0\x01\x04\0\xc2".to_vec();
+        overflow.extend_from_slice(&QOI_PADDING);
+
+        let strict = DecodeOptions {
+            strict: true,
+            ..DecodeOptions::default()
+        };
+        let lenient = DecodeOptions::default();
+        for (bytes, expected) in [
+            (&valid, None),
+            (&valid[..len - 8].to_vec(), Some("BadPadding")),
+            (&valid[..len - 3].to_vec(), Some("BadPadding")),
+            (&bad_padding, Some("BadPadding")),
+            (&trailing, Some("TrailingData")),
+            (&overflow, Some("RunOverflow")),
+        ] {
+            let lenient_result = qoi_decode_from_slice_with_options(bytes, &lenient).unwrap();
+            assert_eq!(
+                qoi_decode_with_options(&bytes[..], &lenient).unwrap(),
+                lenient_result
+            );
+
+            for result in [
+                qoi_decode_from_slice_with_options(bytes, &strict),
+                qoi_decode_with_options(&bytes[..], &strict),
+            ] {
+                match (result, expected) {
+                    (Ok(decoded), None) => assert_eq!(decoded, lenient_result),
+                    (Err(err), Some(expected)) => assert_eq!(format!("{err:?}"), expected),
+                    (result, _) => panic!("unexpected {result:?} instead of {expected:?}"),
+                }
+            }
+        }
+    }
+    #[test]
+    fn decode_limits() {
+        // testcard is 256x256 RGBA
+        let bytes = include_bytes!("../qoi_test_images/testcard.qoi");
+        let fits = DecodeLimits {
+            max_width: 256,
+            max_height: 256,
+            max_pixels: 256 * 256,
+            max_bytes: 256 * 256 * 4,
+        };
+        for (limits, layout, ok) in [
+            (fits, None, true),
+            (
+                DecodeLimits {
+                    max_width: 255,
+                    ..fits
+                },
+                None,
+                false,
+            ),
+            (
+                DecodeLimits {
+                    max_height: 255,
+                    ..fits
+                },
+                None,
+                false,
+            ),
+            (
+                DecodeLimits {
+                    max_pixels: 256 * 255,
+                    ..fits
+                },
+                None,
+                false,
+            ),
+            (
+                DecodeLimits {
+                    max_bytes: 256 * 256 * 3,
+                    ..fits
+                },
+                None,
+                false,
+            ),
+            (
+                DecodeLimits {
+                    max_bytes: 256 * 256 * 3,
+                    ..fits
+                },
+                Some(PixelLayout::Bgr),
+                true,
+            ),
+        ] {
+            let options = DecodeOptions {
+                layout,
+                limits,
+                ..DecodeOptions::default()
+            };
+            for result in [
+                qoi_decode_from_slice_with_options(bytes, &options).map(|_| ()),
+                qoi_decode_with_options(&bytes[..], &options).map(|_| ()),
+                qoi_decode_from_slice_recover(bytes, &options, [0; 4]).map(|_| ()),
+            ] {
+                match result {
+                    Ok(()) => assert!(ok),
+                    Err(err) => assert!(!ok && matches!(err, QoiError::LimitsExceeded)),
+                }
+            }
+        }
+
+        // stream of 10000x10000 image with 3 ops is truncated before buffer for it is allocated
+        let mut bytes = b"qoif".to_vec();
+        bytes.extend(10000u32.to_be_bytes());
+        bytes.extend(10000u32.to_be_bytes());
+        bytes.extend([4, 0, 0xfd, 0xfd, 0xfd]);
+        let err = qoi_decode(&bytes[..], None).unwrap_err();
+        assert!(matches!(err, QoiError::Truncated));
+    }
+    #[test]
+    fn raised_pixel_cap() {
+        let desc = QoiDescriptor {
+            width: 40_000,
+            height: 20_000,
+            channels: ChanelMode::Rgb,
+            colorspace: Colorspace::Srgb,
+        };
+        let unlimited = EncodeOptions {
+            max_pixels: usize::MAX,
+            ..EncodeOptions::default()
+        };
+        let err = QoiEncoder::new(std::io::sink(), &desc).err().unwrap();
+        assert!(matches!(err, QoiError::TooManyPixels));
+        let mut bytes = Vec::new();
+        let mut encoder = QoiEncoder::with_options(&mut bytes, &desc, &unlimited).unwrap();
+        encoder.write_row(&[7; 40_000 * 3]).unwrap();
+        // different row flushes run of the first one
+        encoder.write_row(&[8; 40_000 * 3]).unwrap();
+
+        // pixels fit into usize but their bytes don't
+        let huge = QoiDescriptor {
+            width: u32::MAX as usize,
+            height: u32::MAX as usize,
+            channels: ChanelMode::Rgba,
+            colorspace: Colorspace::Srgb,
+        };
+        let err = QoiEncoder::with_options(std::io::sink(), &huge, &unlimited)
+            .err()
+            .unwrap();
+        assert!(matches!(err, QoiError::TooManyPixels));
+        let mut encoder = QoiEncoder::with_options(
+            std::io::sink(),
+            &QoiDescriptor { height: 1, ..huge },
+            &unlimited,
+        )
+        .unwrap();
+        encoder.write_pixels(&[1, 2, 3, 4]).unwrap();
+        let err = encoder.finish().err().unwrap();
+        assert!(matches!(
+            err,
+            QoiError::BufferSizeMismatch { expected, actual: 4 } if expected == huge.width * 4
+        ));
+
+        // header and first rows of the image
+        bytes.extend_from_slice(&QOI_PADDING);
+        assert!(matches!(
+            qoi_decode_from_slice(&bytes, None).unwrap_err(),
+            QoiError::TooManyPixels
+        ));
+        let options = DecodeOptions {
+            limits: DecodeLimits {
+                max_pixels: usize::MAX,
+                ..DecodeLimits::default()
+            },
+            ..DecodeOptions::default()
+        };
+        let err = qoi_decode_from_slice_with_options(&bytes, &options).unwrap_err();
+        assert!(matches!(err, QoiError::Truncated));
+        let mut decoder = QoiDecoder::with_options(&bytes[..], &options).unwrap();
+        let row = decoder.scanlines().next().unwrap().unwrap();
+        assert!(row.iter().all(|&byte| byte == 7));
+
+        // default cap rejects `QOI_PIXELS_MAX` pixels like qoi.h, explicit one accepts them
+        let square = QoiDescriptor {
+            width: 20_000,
+            height: 20_000,
+            ..desc.clone()
+        };
+        assert!(matches!(
+            pixel_count(&square, EncodeOptions::default().max_pixels),
+            Err(QoiError::TooManyPixels)
+        ));
+        assert_eq!(
+            pixel_count(&square, QOI_PIXELS_MAX).unwrap(),
+            QOI_PIXELS_MAX
+        );
+        assert_eq!(DecodeLimits::default().max_pixels, QOI_PIXELS_MAX - 1);
+
+        // lower caps than default, image of exactly `max_pixels` pixels fits
+        for (width, height, max_pixels, fits) in
+            [(2, 2, 3, false), (2, 2, 4, true), (3, 3, 10, true)]
+        {
+            let small = QoiDescriptor {
+                width,
+                height,
+                ..desc
+            };
+            let options = EncodeOptions {
+                max_pixels,
+                ..EncodeOptions::default()
+            };
+            let pixels = vec![0; width * height * 3];
+            match qoi_encode_with_options(&pixels, &small, &options) {
+                Ok(bytes) => {
+                    assert!(fits);
+                    let options = DecodeOptions {
+                        limits: DecodeLimits {
+                            max_pixels,
+                            ..DecodeLimits::default()
+                        },
+                        ..DecodeOptions::default()
+                    };
+                    let decoded = qoi_decode_from_slice_with_options(&bytes, &options).unwrap();
+                    assert_eq!(decoded, (pixels, small));
+                }
+                Err(err) => assert!(!fits && matches!(err, QoiError::TooManyPixels)),
+            }
+        }
+    }
+    #[test]
+    fn colorspace_conversion() {
+        let bytes = include_bytes!("../qoi_test_images/testcard_rgba.qoi");
+        let (pixels, desc) = qoi_decode_from_slice(bytes, None).unwrap();
+        assert_eq!(desc.colorspace, Colorspace::Srgb);
+
+        let mut expected = pixels.clone();
+        qoi_convert_colorspace(
+            &mut expected,
+            PixelLayout::Rgba,
+            Colorspace::Srgb,
+            Colorspace::Linear,
+        )
+        .unwrap();
+        assert_ne!(expected, pixels);
+        for (converted, pixel) in expected.chunks(4).zip(pixels.chunks(4)) {
+            assert_eq!(converted[3], pixel[3]);
+            assert!(converted[..3].iter().zip(pixel).all(|(c, p)| c <= p));
+        }
+
+        let options = DecodeOptions {
+            colorspace: Some(Colorspace::Linear),
+            ..DecodeOptions::default()
+        };
+        let (linear, desc) = qoi_decode_from_slice_with_options(bytes, &options).unwrap();
+        assert_eq!(desc.colorspace, Colorspace::Linear);
+        assert_eq!(linear, expected);
+        assert_eq!(
+            qoi_decode_with_options(&bytes[..], &options).unwrap(),
+            (linear, desc)
+        );
+
+        // alpha of ARGB stays, conversion to the same colorspace does nothing
+        let mut argb = [128, 255, 188, 0];
+        qoi_convert_colorspace(
+            &mut argb,
+            PixelLayout::Argb,
+            Colorspace::Srgb,
+            Colorspace::Linear,
+        )
+        .unwrap();
+        assert_eq!(argb, [128, 255, 128, 0]);
+        let mut gray = [255, 188];
+        qoi_convert_colorspace(
+            &mut gray,
+            PixelLayout::L,
+            Colorspace::Srgb,
+            Colorspace::Srgb,
+        )
+        .unwrap();
+        assert_eq!(gray, [255, 188]);
+    }
+    #[test]
+    fn recover_truncated() {
+        let bytes = include_bytes!("../qoi_test_images/dice.qoi");
+        let (full, desc) = qoi_decode_from_slice(bytes, None).unwrap();
+        let total = desc.width * desc.height;
+        let fill = [1, 2, 3, 4];
+        let options = DecodeOptions::default();
+
+        let mut last = 0;
+        for len in [2000, 5000, bytes.len() / 2, bytes.len() - 9] {
+            let image = qoi_decode_from_slice_recover(&bytes[..len], &options, fill).unwrap();
+            assert_eq!(image.desc, desc);
+            assert!(image.recovered >= last && image.recovered < total);
+            last = image.recovered;
+            let (decoded, filled) = image.pixels.split_at(image.recovered * 4);
+            assert_eq!(decoded, &full[..image.recovered * 4]);
+            assert!(filled.chunks(4).all(|pixel| pixel == fill));
+            assert_eq!(
+                qoi_decode_recover(&bytes[..len], &options, fill).unwrap(),
+                image
+            );
+        }
+
+        let image = qoi_decode_from_slice_recover(bytes, &options, fill).unwrap();
+        assert_eq!((image.pixels, image.recovered), (full, total));
+        // data must hold at least a quarter of pixels, 62 per byte of ops
+        for len in [10, QOI_HEADER_SIZE, QOI_HEADER_SIZE + 1, 1949] {
+            let err = qoi_decode_from_slice_recover(&bytes[..len], &options, fill).unwrap_err();
+            assert!(matches!(err, QoiError::Truncated));
+        }
+        assert!(qoi_decode_from_slice_recover(&bytes[..1950], &options, fill).is_ok());
+
+        // strict gray layout keeps decoded gray pixels and fills the rest with gray of red
+        let gray: Vec<u8> = (0..64 * 64)
+            .flat_map(|i| [(i * 7 % 251) as u8; 3])
+            .collect();
+        let desc = QoiDescriptor {
+            width: 64,
+            height: 64,
+            channels: ChanelMode::Rgb,
+            colorspace: Colorspace::Srgb,
+        };
+        let bytes = qoi_encode(&gray, &desc).unwrap();
+        let options = DecodeOptions {
+            layout: Some(PixelLayout::L),
+            ..DecodeOptions::default()
+        };
+        let image =
+            qoi_decode_from_slice_recover(&bytes[..bytes.len() / 2], &options, [255, 0, 0, 255])
+                .unwrap();
+        assert!(image.recovered > 0 && image.recovered < 64 * 64);
+        let (decoded, filled) = image.pixels.split_at(image.recovered);
+        assert!(decoded
+            .iter()
+            .zip(gray.chunks(3))
+            .all(|(&l, rgb)| l == rgb[0]));
+        assert!(filled.iter().all(|&l| l == 77));
+    }
+    #[cfg(feature = "image")]
+    #[test]
+    fn image_crate_traits() {
+        use image::{ColorType, DynamicImage, ImageDecoder, ImageEncoder, RgbImage};
+
+        let bytes = include_bytes!("../qoi_test_images/wikipedia_008.qoi");
+        let (pixels, desc) = qoi_decode(&bytes[..], None).unwrap();
+        let image = DynamicImage::from_decoder(QoiImageDecoder::new(&bytes[..]).unwrap()).unwrap();
+        assert_eq!(image, qoi_decode_dynamic(bytes).unwrap());
+        let DynamicImage::ImageRgb8(rgb) = &image else {
+            panic!("rgb image decoded as {:?}", image.color());
+        };
+        assert_eq!(rgb.as_raw(), &pixels);
+
+        let mut encoded = Vec::new();
+        let (width, height) = (desc.width as u32, desc.height as u32);
+        QoiImageEncoder::new(&mut encoded)
+            .write_image(&pixels, width, height, ColorType::Rgb8)
+            .unwrap();
+        assert_eq!(encoded, bytes);
+        assert_eq!(qoi_encode_dynamic(&image).unwrap(), bytes);
+
+        let gray = DynamicImage::ImageLuma8(image.to_luma8());
+        let decoded = qoi_decode_dynamic(&qoi_encode_dynamic(&gray).unwrap()).unwrap();
+        assert_eq!(decoded, DynamicImage::ImageRgb8(gray.to_rgb8()));
+
+        let err = QoiImageEncoder::new(Vec::new())
+            .write_image(&[0; 6], 1, 1, ColorType::Rgb16)
+            .unwrap_err();
+        assert!(matches!(err, image::ImageError::Unsupported(_)));
+        let rgb16 = DynamicImage::ImageRgb16(image::ImageBuffer::new(2, 2));
+        let decoded = qoi_decode_dynamic(&qoi_encode_dynamic(&rgb16).unwrap()).unwrap();
+        assert_eq!(decoded, DynamicImage::ImageRgb8(RgbImage::new(2, 2)));
+
+        // owned image gives the same pixels without copying 8-bit buffers
+        for image in [gray, rgb16] {
+            let (pixels, desc, layout) = qoi_dynamic_pixels(&image);
+            let expected = (pixels.into_owned(), desc, layout);
+            assert_eq!(qoi_dynamic_into_pixels(image), expected);
+        }
+
+        // header of truncated file doesn't decide size of buffer
+        let mut truncated = b"qoif".to_vec();
+        truncated.extend(16384u32.to_be_bytes());
+        truncated.extend(16384u32.to_be_bytes());
+        truncated.extend([4, 0, 0xfe, 1, 2, 3]);
+        let decoder = QoiImageDecoder::new(&truncated[..]).unwrap();
+        assert!(decoder.into_reader().is_err());
+    }
+}
@@ -1,40 +1,90 @@
-use clap::{arg, command, Parser};
-use image::RgbaImage;
+use clap::{Parser, ValueEnum};
 use indicatif::{HumanDuration, ParallelProgressIterator, ProgressBar, ProgressStyle};
+use qoi::*;
 use rayon::prelude::*;
 use std::{
     ffi::OsStr,
     fs::File,
-    io::{BufReader, Write},
+    io::{BufReader, BufWriter, Write},
     path::{Path, PathBuf},
     time::Instant,
 };
-mod qoi;
-use qoi::*;
 
 #[derive(Parser)]
 #[command(author,version,about,long_about = None, arg_required_else_help = true)]
 struct Cli {
     /// Path to input image files
     input: Vec<PathBuf>,
-    /// Directory to output files *UNIMPLEMENTED*
+    /// Directory to output files, relative structure of inputs is kept
     #[arg(short = 'd', long = "output-dir")]
     output_dir: Option<PathBuf>,
+    /// Print counts of qoi ops and compression of every qoi file
+    #[arg(short = 's', long = "stats")]
+    stats: bool,
+    /// Maximum number of pixels of image, 0 removes the limit
+    #[arg(short = 'm', long = "max-pixels", default_value_t = EncodeOptions::default().max_pixels)]
+    max_pixels: usize,
+    /// Colorspace of written files, pixels are converted if input is in the other one
+    /// [default: colorspace of input]
+    #[arg(short = 'c', long = "colorspace", value_enum)]
+    colorspace: Option<ColorspaceArg>,
+    /// Encode bands of every image on all threads, output is a bit bigger than
+    /// the one of reference encoder. Images over 400 million pixels are still
+    /// encoded row by row on one thread
+    #[arg(short = 'p', long = "parallel")]
+    parallel: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ColorspaceArg {
+    Srgb,
+    Linear,
+}
+
+impl From<ColorspaceArg> for Colorspace {
+    fn from(colorspace: ColorspaceArg) -> Self {
+        match colorspace {
+            ColorspaceArg::Srgb => Colorspace::Srgb,
+            ColorspaceArg::Linear => Colorspace::Linear,
+        }
+    }
 }
 
 fn main() {
     let cli = Cli::parse();
-    if cli.input.len() == 1 {
-        let input = &cli.input[0];
+    let root = cli.output_dir.as_ref().map(|_| common_root(&cli.input));
+    let max_pixels = match cli.max_pixels {
+        0 => usize::MAX,
+        max_pixels => max_pixels,
+    };
+    let colorspace = cli.colorspace.map(Colorspace::from);
+    // returns path of qoi file, input or output
+    let convert = |input: &PathBuf| {
+        let output_dir = cli.output_dir.as_deref().zip(root.as_deref());
         match input.extension().and_then(OsStr::to_str) {
-            Some("qoi") => save_from_qoi(input),
-            Some(_) => save_to_qoi(input),
+            Some("qoi") => {
+                let output = output_path(input, output_dir, "png");
+                save_from_qoi(input, &output, max_pixels, colorspace);
+                input.clone()
+            }
+            Some(_) => {
+                let output = output_path(input, output_dir, "qoi");
+                save_to_qoi(input, &output, max_pixels, colorspace, cli.parallel);
+                output
+            }
             None => panic!("no extension"),
-        };
+        }
+    };
+    if cli.input.len() == 1 {
+        let qoi = convert(&cli.input[0]);
+        if cli.stats {
+            print_stats(&cli.input, &[qoi]);
+        }
         println!("done!!");
     } else {
         let started = Instant::now();
-        cli.input
+        let qoi: Vec<PathBuf> = cli
+            .input
             .par_iter()
             .progress_with(
                 ProgressBar::new(cli.input.len() as u64).with_style(
@@ -43,53 +93,304 @@ fn main() {
                         .progress_chars("=> "),
                 ),
             )
-            .for_each(
-                |input: &PathBuf| match input.extension().and_then(OsStr::to_str) {
-                    Some("qoi") => save_from_qoi(input),
-                    Some(_) => save_to_qoi(input),
-                    None => panic!("no extension"),
-                },
-            );
+            .map(convert)
+            .collect();
+        if cli.stats {
+            print_stats(&cli.input, &qoi);
+        }
         println!("Done in {}", HumanDuration(started.elapsed()));
     }
 }
 
-fn save_to_qoi(input: &Path) {
-    // open and decode image
-    let image = image::open(input).expect("your supplied image is not correct");
-    let pixels = image.to_rgba8();
+/// Print statistics of qoi file of every input.
+fn print_stats(inputs: &[PathBuf], qoi: &[PathBuf]) {
+    for (input, qoi) in inputs.iter().zip(qoi) {
+        let bytes = std::fs::read(qoi).expect("cannot open file");
+        let stats = QoiStats::from_qoi(&bytes).expect("unable to count qoi ops");
+        println!("{}:\n{stats}", input.display());
+    }
+}
+
+/// Find deepest directory containing all of the inputs.
+///
+/// Paths are canonicalized first so relative and absolute inputs can be mixed.
+fn common_root(inputs: &[PathBuf]) -> PathBuf {
+    let mut parents = inputs.iter().map(|input| {
+        let input = input.canonicalize().expect("cannot resolve input path");
+        input.parent().map(Path::to_path_buf).unwrap_or_default()
+    });
+    let mut root = parents.next().unwrap_or_default();
+    for parent in parents {
+        while !parent.starts_with(&root) {
+            if !root.pop() {
+                break;
+            }
+        }
+    }
+    root
+}
+
+/// Path of converted file with new `extension`.
+///
+/// Without `output_dir` file is placed next to the input, overwise it is placed
+/// in `output_dir` at the same location relative to `root` as input is.
+/// Missing directories are created.
+fn output_path(input: &Path, output_dir: Option<(&Path, &Path)>, extension: &str) -> PathBuf {
+    let Some((output_dir, root)) = output_dir else {
+        return input.with_extension(extension);
+    };
+    let input = input.canonicalize().expect("cannot resolve input path");
+    let relative = input
+        .strip_prefix(root)
+        .expect("input is outside of common root");
+    let output = output_dir.join(relative).with_extension(extension);
+    if let Some(parent) = output.parent() {
+        std::fs::create_dir_all(parent).expect("cannot create output directory");
+    }
+    output
+}
+
+/// Colorspace of image, sRGB unless it is PNG with gamma 1.0.
+fn input_colorspace(input: &Path) -> Colorspace {
+    if input.extension().and_then(OsStr::to_str) != Some("png") {
+        return Colorspace::Srgb;
+    }
+    let file = BufReader::new(File::open(input).expect("cannot open file"));
+    let reader = png::Decoder::new(file)
+        .read_info()
+        .expect("your supplied image is not correct");
+    png_colorspace(reader.info())
+}
+
+/// Colorspace of PNG, linear if it has gamma 1.0 and isn't marked as sRGB.
+fn png_colorspace(info: &png::Info) -> Colorspace {
+    match info.source_gamma {
+        Some(gamma) if info.srgb.is_none() && gamma.into_scaled() == 100_000 => Colorspace::Linear,
+        _ => Colorspace::Srgb,
+    }
+}
+
+/// Reader of PNG with more than `QOI_PIXELS_MAX` pixels, which rows can be read in order.
+///
+/// `None` for other images and for interlaced PNG, which has to be decoded whole.
+fn open_big_png(input: &Path) -> Option<png::Reader<BufReader<File>>> {
+    if input.extension().and_then(OsStr::to_str) != Some("png") {
+        return None;
+    }
+    let file = BufReader::new(File::open(input).expect("cannot open file"));
+    let mut decoder = png::Decoder::new(file);
+    // palette, low bit depths and 16 bits are turned into 8 bits per channel
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let reader = decoder
+        .read_info()
+        .expect("your supplied image is not correct");
+    let info = reader.info();
+    let pixels = info.width as usize * info.height as usize;
+    (pixels > QOI_PIXELS_MAX && !info.interlaced).then_some(reader)
+}
+
+/// Convert PNG to QOI row by row, so neither of images is ever whole in memory.
+fn save_png_rows_to_qoi(
+    mut reader: png::Reader<BufReader<File>>,
+    output: &Path,
+    max_pixels: usize,
+    colorspace: Option<Colorspace>,
+) {
+    let layout = match reader.output_color_type().0 {
+        png::ColorType::Grayscale => PixelLayout::L,
+        png::ColorType::GrayscaleAlpha => PixelLayout::La,
+        png::ColorType::Rgb => PixelLayout::Rgb,
+        png::ColorType::Rgba => PixelLayout::Rgba,
+        png::ColorType::Indexed => unreachable!("palette is expanded to rgb(a)"),
+    };
+    let info = reader.info();
+    let source = png_colorspace(info);
+    let desc = QoiDescriptor {
+        width: info.width as usize,
+        height: info.height as usize,
+        channels: layout.channels(),
+        colorspace: colorspace.unwrap_or(source),
+    };
+    let options = EncodeOptions {
+        layout: Some(layout),
+        max_pixels,
+    };
+
+    let file = BufWriter::new(File::create(output).expect("cannot create file"));
+    let mut encoder =
+        QoiEncoder::with_options(file, &desc, &options).expect("unable to encode image");
+    let mut converted = Vec::new();
+    while let Some(row) = reader
+        .next_row()
+        .expect("your supplied image is not correct")
+    {
+        let mut row = row.data();
+        if desc.colorspace != source {
+            converted.clear();
+            converted.extend_from_slice(row);
+            qoi_convert_colorspace(&mut converted, layout, source, desc.colorspace)
+                .expect("unable to convert colorspace");
+            row = &converted;
+        }
+        encoder.write_row(row).expect("unable to write to file");
+    }
+    encoder.finish().expect("unable to write to file");
+}
+
+fn save_to_qoi(
+    input: &Path,
+    output: &Path,
+    max_pixels: usize,
+    colorspace: Option<Colorspace>,
+    parallel: bool,
+) {
+    if let Some(reader) = open_big_png(input) {
+        if parallel {
+            eprintln!("{input:?} is too big to encode in parallel, encoding it row by row");
+        }
+        save_png_rows_to_qoi(reader, output, max_pixels, colorspace);
+        return;
+    }
+
+    // open and decode image, allowing allocations for as many pixels as qoi image may have
+    let mut reader = image::io::Reader::open(input)
+        .and_then(|reader| reader.with_guessed_format())
+        .expect("cannot open file");
+    let mut limits = image::io::Limits::default();
+    limits.max_alloc = max_pixels.checked_mul(4).map(|max| max as u64);
+    reader.limits(limits);
+    let image = reader.decode().expect("your supplied image is not correct");
+
+    // rgb and gray images don't get alpha
+    let (mut pixels, mut desc, layout) = qoi_dynamic_into_pixels(image);
+
+    // tag image with colorspace of input or convert it to requested one
+    let source = input_colorspace(input);
+    desc.colorspace = colorspace.unwrap_or(source);
+    if desc.colorspace != source {
+        qoi_convert_colorspace(&mut pixels, layout, source, desc.colorspace)
+            .expect("unable to convert colorspace");
+    }
+    let options = EncodeOptions {
+        layout: Some(layout),
+        max_pixels,
+    };
 
     // create file for encoded qoi image
-    let mut file = File::create(input.with_extension("qoi")).expect("cannot create file");
-
-    // encode qoi image and write it to file
-    let bytes = qoi_encode(
-        &pixels,
-        &QoiDescriptor {
-            width: image.width() as usize,
-            height: image.height() as usize,
-            channels: ChanelMode::Rgba,
-            colorspace: Colorspace::Srgb,
-        },
-    )
-    .expect("unable to decode image");
+    let mut file = BufWriter::new(File::create(output).expect("cannot create file"));
 
-    file.write_all(&bytes).expect("unable to write to file");
+    if desc.width * desc.height <= QOI_PIXELS_MAX {
+        // encode qoi image at once, in bands on all threads if asked to
+        let bytes = match parallel {
+            true => qoi_encode_parallel_with_options(&pixels, &desc, &options),
+            false => qoi_encode_with_options(&pixels, &desc, &options),
+        }
+        .expect("unable to encode image");
+        file.write_all(&bytes).expect("unable to write to file");
+        file.flush().expect("unable to write to file");
+    } else {
+        // image of other format than PNG is decoded whole, but encoded image may not
+        // fit in memory next to it, so it is written row by row on this thread
+        if parallel {
+            eprintln!("{input:?} is too big to encode in parallel, encoding it row by row");
+        }
+        let mut encoder =
+            QoiEncoder::with_options(file, &desc, &options).expect("unable to encode image");
+        for row in pixels.chunks(desc.width * layout.bytes_per_pixel()) {
+            encoder.write_row(row).expect("unable to write to file");
+        }
+        encoder.finish().expect("unable to write to file");
+    }
 }
 
-fn save_from_qoi(input: &Path) {
-    // open file
-    let file = File::open(input).expect("cannot open file");
-    let buf = BufReader::new(file);
+fn save_from_qoi(input: &Path, output: &Path, max_pixels: usize, colorspace: Option<Colorspace>) {
+    let options = DecodeOptions {
+        limits: DecodeLimits {
+            max_pixels,
+            ..DecodeLimits::default()
+        },
+        colorspace,
+        ..DecodeOptions::default()
+    };
+
+    // read header keeping rgb or rgba channels of qoi file, converted to requested colorspace
+    let file = BufReader::new(File::open(input).expect("cannot open file"));
+    let mut decoder = QoiDecoder::with_options(file, &options).expect("unable to decode qoi image");
+    let desc = decoder.desc().clone();
 
-    // decode pixels
-    let (pixels, desc) =
-        qoi_decode(buf, Some(ChanelMode::Rgba)).expect("unable to decode qoi image");
+    // decode image at once from whole file, bigger ones are decoded row by row below
+    let pixels = (desc.width * desc.height <= QOI_PIXELS_MAX).then(|| {
+        let bytes = std::fs::read(input).expect("cannot open file");
+        let (pixels, _) = qoi_decode_from_slice_with_options(&bytes, &options)
+            .expect("unable to decode qoi image");
+        pixels
+    });
 
-    // encode in new file and save it
-    let output = &input.with_extension("png");
-    RgbaImage::from_raw(desc.width as u32, desc.height as u32, pixels)
-        .expect("unable to encode image")
-        .save(output)
+    // save it as png marked as sRGB or linear
+    let file = BufWriter::new(File::create(output).expect("cannot create file"));
+    let mut encoder = png::Encoder::new(file, desc.width as u32, desc.height as u32);
+    encoder.set_color(match desc.channels {
+        ChanelMode::Rgb => png::ColorType::Rgb,
+        ChanelMode::Rgba => png::ColorType::Rgba,
+    });
+    encoder.set_depth(png::BitDepth::Eight);
+    match desc.colorspace {
+        Colorspace::Srgb => encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual),
+        Colorspace::Linear => encoder.set_source_gamma(png::ScaledFloat::new(1.0)),
+    }
+    let mut writer = encoder
+        .write_header()
         .unwrap_or_else(|_| panic!("unable to save image to {output:?}"));
+    match pixels {
+        Some(pixels) => writer
+            .write_image_data(&pixels)
+            .and_then(|()| writer.finish()),
+        None => {
+            // decoded image may not fit in memory, write it row by row
+            let mut stream = writer
+                .stream_writer()
+                .unwrap_or_else(|_| panic!("unable to save image to {output:?}"));
+            let mut row = vec![0; desc.width * desc.channels as usize];
+            while decoder
+                .read_rows(&mut row)
+                .expect("unable to decode qoi image")
+                > 0
+            {
+                stream
+                    .write_all(&row)
+                    .unwrap_or_else(|_| panic!("unable to save image to {output:?}"));
+            }
+            stream.finish()
+        }
+    }
+    .unwrap_or_else(|_| panic!("unable to save image to {output:?}"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn output_dir_keeps_structure() {
+        let root = std::env::temp_dir().join("qoiconv-output-dir-test");
+        let inputs = [root.join("a/one.png"), root.join("a/b/two.qoi")];
+        for input in &inputs {
+            std::fs::create_dir_all(input.parent().unwrap()).unwrap();
+            File::create(input).unwrap();
+        }
+        let common = common_root(&inputs);
+        assert_eq!(common, root.join("a").canonicalize().unwrap());
+
+        let out = root.join("out");
+        let output_dir = Some((out.as_path(), common.as_path()));
+        assert_eq!(
+            output_path(&inputs[0], output_dir, "qoi"),
+            out.join("one.qoi")
+        );
+        assert_eq!(
+            output_path(&inputs[1], output_dir, "png"),
+            out.join("b/two.png")
+        );
+        assert!(out.join("b").is_dir());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
 }
@@ -0,0 +1,111 @@
+//! Vectorized helpers of the encoder.
+//!
+//! Every helper has scalar fallback giving the same result, SSE2 is always present on
+//! x86_64 and SSSE3 is detected at runtime with `std` or enabled with `target-feature`.
+#[cfg(target_arch = "x86_64")]
+use core::arch::x86_64::*;
+
+/// Length of block compared at once, whole number of pixels of every layout.
+const BLOCK: usize = 48;
+
+/// Number of whole pixels at start of `bytes` equal to `pixel` of 1 to 4 bytes.
+pub(crate) fn run_length(bytes: &[u8], pixel: &[u8]) -> usize {
+    let mut pattern = [0; BLOCK];
+    for chunk in pattern.chunks_exact_mut(pixel.len()) {
+        chunk.copy_from_slice(pixel);
+    }
+
+    let mut equal = 0;
+    let mut blocks = bytes.chunks_exact(BLOCK);
+    for block in &mut blocks {
+        match mismatch(block.try_into().unwrap(), &pattern) {
+            Some(pos) => return (equal + pos) / pixel.len(),
+            None => equal += BLOCK,
+        }
+    }
+    let rest = blocks.remainder();
+    equal += rest
+        .iter()
+        .zip(pattern)
+        .take_while(|(byte, expected)| *byte == expected)
+        .count();
+    equal / pixel.len()
+}
+
+/// Position of first byte which differs between `block` and `pattern`.
+#[cfg(target_arch = "x86_64")]
+#[inline]
+fn mismatch(block: &[u8; BLOCK], pattern: &[u8; BLOCK]) -> Option<usize> {
+    // SAFETY: SSE2 is part of x86_64 baseline, loads are unaligned and within both arrays
+    let mask = unsafe {
+        let mut mask = 0u64;
+        for i in 0..BLOCK / 16 {
+            let a = _mm_loadu_si128(block.as_ptr().add(i * 16).cast());
+            let b = _mm_loadu_si128(pattern.as_ptr().add(i * 16).cast());
+            let equal = _mm_movemask_epi8(_mm_cmpeq_epi8(a, b)) as u16;
+            mask |= (equal as u64) << (i * 16);
+        }
+        mask
+    };
+    let differ = !mask & ((1 << BLOCK) - 1);
+    (differ != 0).then(|| differ.trailing_zeros() as usize)
+}
+
+/// Position of first byte which differs between `block` and `pattern`.
+#[cfg(not(target_arch = "x86_64"))]
+#[inline]
+fn mismatch(block: &[u8; BLOCK], pattern: &[u8; BLOCK]) -> Option<usize> {
+    if block == pattern {
+        return None;
+    }
+    block.iter().zip(pattern).position(|(a, b)| a != b)
+}
+
+/// Expand RGB pixels of `rgb` into RGBA pixels of `rgba` with opaque alpha.
+///
+/// `rgb` must have exactly 3 bytes for every pixel of `rgba`.
+pub(crate) fn rgb_to_rgba(rgb: &[u8], rgba: &mut [[u8; 4]]) {
+    debug_assert_eq!(rgb.len(), rgba.len() * 3);
+    #[cfg(target_arch = "x86_64")]
+    if has_ssse3() {
+        // SAFETY: SSSE3 support was just checked
+        return unsafe { rgb_to_rgba_ssse3(rgb, rgba) };
+    }
+    rgb_to_rgba_scalar(rgb, rgba);
+}
+
+fn rgb_to_rgba_scalar(rgb: &[u8], rgba: &mut [[u8; 4]]) {
+    for (rgb, rgba) in rgb.chunks_exact(3).zip(rgba) {
+        *rgba = [rgb[0], rgb[1], rgb[2], 255];
+    }
+}
+
+#[cfg(all(target_arch = "x86_64", feature = "std"))]
+fn has_ssse3() -> bool {
+    std::is_x86_feature_detected!("ssse3")
+}
+
+#[cfg(all(target_arch = "x86_64", not(feature = "std")))]
+fn has_ssse3() -> bool {
+    cfg!(target_feature = "ssse3")
+}
+
+/// Expand 4 pixels at a time with byte shuffle, every load reads 16 of 12 bytes
+/// so last pixels which don't have 4 extra bytes after them are expanded by scalar code.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "ssse3")]
+unsafe fn rgb_to_rgba_ssse3(rgb: &[u8], rgba: &mut [[u8; 4]]) {
+    let shuffle = _mm_setr_epi8(0, 1, 2, -1, 3, 4, 5, -1, 6, 7, 8, -1, 9, 10, 11, -1);
+    let alpha = _mm_set1_epi32(0xff00_0000_u32 as i32);
+
+    let vectors = rgb.len().saturating_sub(4) / 12;
+    for i in 0..vectors {
+        // SAFETY: `i * 12 + 16 <= rgb.len()` and `rgba` has `4 * vectors` pixels
+        unsafe {
+            let pixels = _mm_loadu_si128(rgb.as_ptr().add(i * 12).cast());
+            let expanded = _mm_or_si128(_mm_shuffle_epi8(pixels, shuffle), alpha);
+            _mm_storeu_si128(rgba.as_mut_ptr().add(i * 4).cast(), expanded);
+        }
+    }
+    rgb_to_rgba_scalar(&rgb[vectors * 12..], &mut rgba[vectors * 4..]);
+}
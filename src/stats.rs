@@ -0,0 +1,128 @@
+//! Statistics of ops in QOI stream.
+use super::*;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+/// Counts of every op in QOI image, to see why it compresses well or badly.
+///
+/// ### Example of printing statistics of encoded image:
+/// ```
+/// use qoi::*;
+///
+/// let pixels = [0, 0, 0, 0, 0, 0, 10, 20, 30, 10, 20, 30];
+/// let desc = QoiDescriptor {
+///     width: 4,
+///     height: 1,
+///     channels: ChanelMode::Rgb,
+///     colorspace: Colorspace::Srgb,
+/// };
+/// let (bytes, stats) = qoi_encode_with_stats(&pixels, &desc).unwrap();
+/// assert_eq!(stats.run, 2);
+/// assert_eq!(stats.run_pixels, 3);
+/// assert_eq!(stats.bytes, bytes.len());
+/// println!("{stats}");
+/// ```
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct QoiStats {
+    /// Number of `QOI_OP_INDEX` ops.
+    pub index: usize,
+    /// Number of `QOI_OP_DIFF` ops.
+    pub diff: usize,
+    /// Number of `QOI_OP_LUMA` ops.
+    pub luma: usize,
+    /// Number of `QOI_OP_RUN` ops.
+    pub run: usize,
+    /// Number of `QOI_OP_RGB` ops.
+    pub rgb: usize,
+    /// Number of `QOI_OP_RGBA` ops.
+    pub rgba: usize,
+    /// Total length of all runs in pixels.
+    pub run_pixels: usize,
+    /// Number of pixels in image.
+    pub pixels: usize,
+    /// Size of whole file including header, padding and anything after it.
+    pub bytes: usize,
+}
+
+impl QoiStats {
+    /// Count ops of QOI image in `data` without decoding pixels.
+    ///
+    /// Nothing is allocated so images of any size are accepted.
+    pub fn from_qoi(data: &[u8]) -> Result<Self, QoiError> {
+        let desc = decode::read_header_from_slice(data, usize::MAX)?;
+        let mut stats = Self {
+            pixels: desc.width * desc.height,
+            ..Self::default()
+        };
+
+        let mut pos = QOI_HEADER_SIZE;
+        let mut pixels = 0;
+        while pixels < stats.pixels {
+            let op_byte = *data.get(pos).ok_or(QoiError::Truncated)?;
+            pixels += 1;
+            match op_byte {
+                QOI_OP_RGB => stats.rgb += 1,
+                QOI_OP_RGBA => stats.rgba += 1,
+                _ => match op_byte & QOI_MASK {
+                    QOI_OP_INDEX => stats.index += 1,
+                    QOI_OP_DIFF => stats.diff += 1,
+                    QOI_OP_LUMA => stats.luma += 1,
+                    _ => {
+                        // like lenient decoding, run doesn't go past the last pixel
+                        let run = ((op_byte & 0x3f) as usize + 1).min(stats.pixels - pixels + 1);
+                        stats.run += 1;
+                        stats.run_pixels += run;
+                        pixels += run - 1;
+                    }
+                },
+            }
+            pos += op_len(op_byte);
+        }
+        if pos > data.len() {
+            return Err(QoiError::Truncated);
+        }
+        stats.bytes = data.len();
+        Ok(stats)
+    }
+
+    /// Share of pixels outside of runs which were found in index.
+    pub fn index_hit_rate(&self) -> f64 {
+        match self.pixels.saturating_sub(self.run_pixels) {
+            0 => 0.0,
+            pixels => self.index as f64 / pixels as f64,
+        }
+    }
+
+    /// Average size of pixel in bytes, including header and padding.
+    pub fn bytes_per_pixel(&self) -> f64 {
+        self.bytes as f64 / self.pixels as f64
+    }
+}
+
+impl fmt::Display for QoiStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} bytes, {:.3} bytes per pixel, index hit rate {:.1}%",
+            self.bytes,
+            self.bytes_per_pixel(),
+            self.index_hit_rate() * 100.0
+        )?;
+        write!(
+            f,
+            "ops: index {}, diff {}, luma {}, run {} ({} pixels), rgb {}, rgba {}",
+            self.index, self.diff, self.luma, self.run, self.run_pixels, self.rgb, self.rgba
+        )
+    }
+}
+
+/// Encode raw RGB or RGBA pixels like [`qoi_encode`](fn.qoi_encode.html) and count ops of result.
+#[cfg(feature = "alloc")]
+pub fn qoi_encode_with_stats(
+    pixels: &[u8],
+    desc: &QoiDescriptor,
+) -> Result<(Vec<u8>, QoiStats), QoiError> {
+    let bytes = qoi_encode(pixels, desc)?;
+    let stats = QoiStats::from_qoi(&bytes)?;
+    Ok((bytes, stats))
+}
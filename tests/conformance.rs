@@ -0,0 +1,88 @@
+//! Bitstream conformance against `.qoi` files in `qoi_test_images/` made by reference `qoi.h`.
+use image::DynamicImage;
+use qoi::*;
+use std::{fs, path::Path};
+
+/// Png from `qoi_test_images/` and matching reference `.qoi` file.
+struct TestImage {
+    name: String,
+    /// Pixels of png as reference `qoiconv.c` loads them, only RGB is kept as is.
+    pixels: Vec<u8>,
+    desc: QoiDescriptor,
+    qoi: Vec<u8>,
+}
+
+fn test_images() -> Vec<TestImage> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("qoi_test_images");
+    let mut images: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "png"))
+        .map(|path| {
+            let image = image::open(&path).unwrap();
+            let (width, height) = (image.width() as usize, image.height() as usize);
+            let (pixels, channels) = match image {
+                DynamicImage::ImageRgb8(image) => (image.into_raw(), ChanelMode::Rgb),
+                image => (image.to_rgba8().into_raw(), ChanelMode::Rgba),
+            };
+            TestImage {
+                name: path.file_stem().unwrap().to_string_lossy().into_owned(),
+                pixels,
+                desc: QoiDescriptor {
+                    width,
+                    height,
+                    channels,
+                    colorspace: Colorspace::Srgb,
+                },
+                qoi: fs::read(path.with_extension("qoi")).unwrap(),
+            }
+        })
+        .collect();
+    images.sort_by(|a, b| a.name.cmp(&b.name));
+    assert!(!images.is_empty(), "no test images found");
+    images
+}
+
+#[test]
+fn encode_matches_reference() {
+    for image in test_images() {
+        let bytes = qoi_encode(&image.pixels, &image.desc).unwrap();
+        assert!(
+            bytes == image.qoi,
+            "{}: encoded bytes differ from reference",
+            image.name
+        );
+    }
+}
+
+#[test]
+fn decode_matches_png() {
+    for image in test_images() {
+        for (pixels, desc) in [
+            qoi_decode(image.qoi.as_slice(), None).unwrap(),
+            qoi_decode_from_slice(&image.qoi, None).unwrap(),
+        ] {
+            assert_eq!(desc, image.desc, "{}: descriptor differs", image.name);
+            assert!(
+                pixels == image.pixels,
+                "{}: decoded pixels differ from png",
+                image.name
+            );
+        }
+    }
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn parallel_encode_roundtrips() {
+    for image in test_images() {
+        let bytes = qoi_encode_parallel(&image.pixels, &image.desc).unwrap();
+        let (pixels, desc) = qoi_decode(bytes.as_slice(), None).unwrap();
+        assert_eq!(desc, image.desc, "{}: descriptor differs", image.name);
+        assert!(
+            pixels == image.pixels,
+            "{}: pixels of parallel encoding differ from png",
+            image.name
+        );
+    }
+}